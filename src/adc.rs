@@ -198,8 +198,8 @@ macro_rules! adc_hal {
                 // starting calibration, we already delayed in the power-up process, but if the adc
                 // clock is too low that was not enough
                 if $ADC::base_frequency(rcu).0 < 2_500_000 {
-                    let cycles = rcu.clocks.sysclk().0 / $ADC::base_frequency(rcu).0 * 14;
-                    let delayed = rcu.clocks.sysclk().0 / 800_000;
+                    let cycles = rcu.clocks.hclk().0 / $ADC::base_frequency(rcu).0 * 14;
+                    let delayed = rcu.clocks.hclk().0 / 800_000;
                     if cycles > delayed {
                         delay((cycles - delayed) as u64);
                     }
@@ -262,7 +262,7 @@ macro_rules! adc_hal {
                 // this time can be found in the datasheets.
                 // Here we are delaying for approximately 1us, considering 1.25 instructions per
                 // cycle. Is there a chip which needs more than 1us?
-                delay((self.clocks.sysclk().0 / 800_000) as u64);
+                delay((self.clocks.hclk().0 / 800_000) as u64);
             }
 
             fn power_down(&mut self) {
@@ -452,7 +452,7 @@ impl Adc<ADC0> {
             // sensor, this time can be found in the datasheets.
             // Here we are delaying for approximately 10us, considering 1.25 instructions per
             // cycle. Do we support a chip which needs more than 10us ?
-            delay((self.clocks.sysclk().0 / 80_000) as u64);
+            delay((self.clocks.hclk().0 / 80_000) as u64);
             true
         } else {
             false
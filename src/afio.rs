@@ -5,6 +5,7 @@ use crate::rcu::{Rcu, Enable, Reset};
 use crate::gpio::{Debugger, Input, Floating, Port};
 use crate::gpio::gpioa::{PA13, PA14, PA15};
 use crate::gpio::gpiob::{PB3, PB4};
+use crate::exti::LineInUse;
 
 pub trait AfioExt {
     fn constrain(self, rcu: &mut Rcu) -> Afio;
@@ -15,12 +16,14 @@ impl AfioExt for AFIO {
         AFIO::enable(rcu);
         AFIO::reset(rcu);
 
-        Afio { afio: self }
+        Afio { afio: self, exti_lines: 0 }
     }
 }
 
 pub struct Afio {
-    afio: AFIO
+    afio: AFIO,
+    /// EXTI lines routed through `claim_exti_line`
+    exti_lines: u16,
 }
 
 impl Afio {
@@ -56,8 +59,19 @@ impl Afio {
         }
     }
 
+    /// Routes EXTI line `pin` to `port`
+    ///
+    /// Overrides any claim made through [`ExtiPin`](crate::exti::ExtiPin). The line then counts
+    /// as claimed by `port`, so pins of other ports can't take it over.
     #[inline]
     pub fn extiss(&mut self, port: Port, pin: u8) {
+        self.set_exti_source(port, pin);
+        if pin < 16 {
+            self.exti_lines |= 1 << pin;
+        }
+    }
+
+    fn set_exti_source(&mut self, port: Port, pin: u8) {
         match pin {
             0 => self.afio.extiss0.modify(|_, w| unsafe { w.exti0_ss().bits(port as u8)}),
             1 => self.afio.extiss0.modify(|_, w| unsafe { w.exti1_ss().bits(port as u8)}),
@@ -78,6 +92,44 @@ impl Afio {
             _ => {}
         }
     }
+
+    /// Connects IRC40K to the TIMER4 channel 3 input instead of PA3
+    pub(crate) fn set_timer4ch3_iremap(&mut self, irc40k: bool) {
+        self.afio.pcf0.modify(|_, w| w.timer4ch3_iremap().bit(irc40k));
+    }
+
+    /// Routes EXTI line `pin` to `port`, unless a pin of another port already claimed it
+    pub(crate) fn claim_exti_line(&mut self, port: Port, pin: u8) -> Result<(), LineInUse> {
+        let bm = 1u16 << pin;
+        if self.exti_lines & bm != 0 && self.exti_source(pin) != port as u8 {
+            return Err(LineInUse);
+        }
+
+        self.set_exti_source(port, pin);
+        self.exti_lines |= bm;
+        Ok(())
+    }
+
+    /// Gives EXTI line `pin` back if `port` claimed it, returning `true` if it did
+    pub(crate) fn release_exti_line(&mut self, port: Port, pin: u8) -> bool {
+        let bm = 1u16 << pin;
+        let claimed = self.exti_lines & bm != 0 && self.exti_source(pin) == port as u8;
+        if claimed {
+            self.exti_lines &= !bm;
+        }
+        claimed
+    }
+
+    /// Returns the port selected for EXTI line `pin`
+    fn exti_source(&self, pin: u8) -> u8 {
+        let bits = match pin / 4 {
+            0 => self.afio.extiss0.read().bits(),
+            1 => self.afio.extiss1.read().bits(),
+            2 => self.afio.extiss2.read().bits(),
+            _ => self.afio.extiss3.read().bits(),
+        };
+        ((bits >> ((pin % 4) * 4)) & 0b1111) as u8
+    }
 }
 
 pub(crate) mod closed_traits {
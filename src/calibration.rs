@@ -0,0 +1,260 @@
+//! Internal oscillator calibration
+//!
+//! IRC8M drifts with temperature and supply voltage, and IRC40K, which clocks the free watchdog,
+//! is only specified to within tens of percent. Both oscillators can be measured against the
+//! 32.768 kHz LXTAL clocking the RTC:
+//!
+//! - HCLK is measured by counting `mcycle` over a fixed number of LXTAL periods, which are
+//!   observed through the RTC prescaler divider. If CK_SYS is derived from IRC8M, IRC8M is
+//!   trimmed through `IRC8MADJ`.
+//! - IRC40K is internally connected to TIMER4 channel 3 and measured by input capture.
+
+use crate::afio::Afio;
+use crate::pac::TIMER4;
+use crate::rcu::{BaseFrequency, Enable, Rcu, Reset};
+use crate::rtc::Rtc;
+use crate::time::Hertz;
+use riscv::interrupt;
+use riscv::register::mcycle;
+
+const LXTAL_HERTZ: u64 = 32_768;
+const IRC8M: i32 = 8_000_000;
+
+/// Number of LXTAL periods an HCLK measurement spans (62.5 ms)
+const LXTAL_PERIODS: u64 = 2048;
+/// Approximate IRC8M frequency change per IRC8MADJ step
+const IRC8MADJ_STEP: i32 = 40_000;
+/// Maximum number of IRC8MADJ adjustments
+const IRC8MADJ_ITERATIONS: usize = 4;
+/// Number of IRC40K periods covered by one capture (channel input prescaler)
+const IRC40K_PERIODS_PER_CAPTURE: u32 = 8;
+/// Number of captures averaged when measuring IRC40K
+const IRC40K_CAPTURES: u32 = 16;
+
+/// Calibration error
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CalibrationError {
+    /// The RTC prescaler is 0, so its divider doesn't follow LXTAL
+    RtcPrescaler,
+    /// The RTC divider stopped counting, LXTAL isn't running
+    LxtalTimeout,
+    /// IRC40K didn't start or no capture was seen on TIMER4
+    Irc40kTimeout,
+}
+
+/// Calibration results
+#[derive(Copy, Clone)]
+pub struct Calibration {
+    /// Measured IRC8M frequency after trimming, `None` if CK_SYS is not derived from IRC8M
+    pub irc8m: Option<Hertz>,
+    /// IRC8MADJ trim value in effect
+    pub irc8madj: u8,
+    /// Measured IRC40K frequency
+    pub irc40k: Hertz,
+}
+
+/// Trims IRC8M and measures IRC40K against LXTAL
+///
+/// The measured IRC40K frequency is stored in `rcu.clocks` (see
+/// [`Clocks::irc40k`](crate::rcu::Clocks::irc40k)). TIMER4 is reset and left disabled, and IRC40K
+/// is turned off again if it was off before.
+///
+/// Interrupts are disabled for the duration of each measurement, up to about 350 ms in total.
+/// Fails if the RTC prescaler is 0 or if LXTAL or IRC40K stop for more than 1 ms, in which case
+/// IRC8MADJ is left unchanged.
+pub fn calibrate(
+    rcu: &mut Rcu,
+    rtc: &Rtc,
+    afio: &mut Afio,
+    timer: &mut TIMER4,
+) -> Result<Calibration, CalibrationError> {
+    if rtc.prescaler() == 0 {
+        return Err(CalibrationError::RtcPrescaler);
+    }
+
+    // mcycle counts at HCLK
+    let nominal = rcu.clocks.hclk().0;
+    let timeout = u64::from(nominal / 1000);
+    if !wait_until(timeout, || rtc.is_synchronized()) {
+        return Err(CalibrationError::LxtalTimeout);
+    }
+
+    let cfg0 = rcu.regs.cfg0.read();
+    let from_irc8m = match cfg0.scss().bits() {
+        0b00 => true,
+        0b10 => cfg0.pllsel().bit_is_clear(),
+        _ => false,
+    };
+
+    let (irc8m, hclk) = if from_irc8m {
+        let original = rcu.regs.ctl.read().irc8madj().bits();
+        match trim_irc8m(rcu, rtc, nominal, timeout) {
+            Ok(result) => result,
+            Err(error) => {
+                set_irc8madj(rcu, original);
+                return Err(error);
+            }
+        }
+    } else {
+        (None, measure_hclk(rtc, timeout)?)
+    };
+    let irc8madj = rcu.regs.ctl.read().irc8madj().bits();
+
+    // Scale the nominal timer clock by the measured HCLK deviation
+    let timer_clk =
+        u64::from(TIMER4::base_frequency(rcu).0) * u64::from(hclk) / u64::from(nominal);
+    let ticks = measure_irc40k(rcu, afio, timer, timeout)?;
+    let periods = u64::from(IRC40K_PERIODS_PER_CAPTURE * IRC40K_CAPTURES);
+    let irc40k = (timer_clk * periods / u64::from(ticks)) as u32;
+    rcu.clocks.irc40k = Hertz(irc40k);
+
+    Ok(Calibration {
+        irc8m,
+        irc8madj,
+        irc40k: Hertz(irc40k),
+    })
+}
+
+/// Trims IRC8MADJ, returning the measured IRC8M and HCLK frequencies
+fn trim_irc8m(
+    rcu: &mut Rcu,
+    rtc: &Rtc,
+    nominal: u32,
+    timeout: u64,
+) -> Result<(Option<Hertz>, u32), CalibrationError> {
+    let measure = || -> Result<i32, CalibrationError> {
+        let hclk = measure_hclk(rtc, timeout)?;
+        Ok((u64::from(hclk) * IRC8M as u64 / u64::from(nominal)) as i32)
+    };
+
+    let mut adj = i32::from(rcu.regs.ctl.read().irc8madj().bits());
+    let mut freq = measure()?;
+    let mut best = (adj, freq);
+    for _ in 0..IRC8MADJ_ITERATIONS {
+        let error = freq - IRC8M;
+        let steps = (error + error.signum() * IRC8MADJ_STEP / 2) / IRC8MADJ_STEP;
+        let next = (adj - steps).clamp(0, 0x1F);
+        if next == adj {
+            break;
+        }
+        adj = next;
+        set_irc8madj(rcu, adj as u8);
+        freq = measure()?;
+        if (freq - IRC8M).abs() < (best.1 - IRC8M).abs() {
+            best = (adj, freq);
+        }
+    }
+    set_irc8madj(rcu, best.0 as u8);
+
+    let irc8m = best.1 as u32;
+    let hclk = (u64::from(nominal) * u64::from(irc8m) / IRC8M as u64) as u32;
+    Ok((Some(Hertz(irc8m)), hclk))
+}
+
+fn set_irc8madj(rcu: &mut Rcu, adj: u8) {
+    rcu.regs.ctl.modify(|_, w| unsafe { w.irc8madj().bits(adj) });
+}
+
+/// Returns the HCLK frequency in Hz, measured against LXTAL
+fn measure_hclk(rtc: &Rtc, timeout: u64) -> Result<u32, CalibrationError> {
+    let cycles = interrupt::free(|_| {
+        // Synchronize to an LXTAL period boundary
+        let mut div = wait_lxtal_period(rtc, rtc.divider_low(), timeout)?;
+
+        let start = mcycle::read64();
+        for _ in 0..LXTAL_PERIODS {
+            div = wait_lxtal_period(rtc, div, timeout)?;
+        }
+        Ok(mcycle::read64().wrapping_sub(start))
+    })?;
+
+    Ok((cycles * LXTAL_HERTZ / LXTAL_PERIODS) as u32)
+}
+
+/// Waits for the RTC divider to move away from `div` and returns its new value
+fn wait_lxtal_period(rtc: &Rtc, div: u16, timeout: u64) -> Result<u16, CalibrationError> {
+    let mut next = div;
+    if wait_until(timeout, || {
+        next = rtc.divider_low();
+        next != div
+    }) {
+        Ok(next)
+    } else {
+        Err(CalibrationError::LxtalTimeout)
+    }
+}
+
+/// Busy waits until `f` returns `true`, for at most `cycles` HCLK cycles
+fn wait_until(cycles: u64, mut f: impl FnMut() -> bool) -> bool {
+    let start = mcycle::read64();
+    while !f() {
+        if mcycle::read64().wrapping_sub(start) > cycles {
+            return false;
+        }
+    }
+    true
+}
+
+/// Returns the number of TIMER4 clock cycles spanned by `IRC40K_CAPTURES` captures
+fn measure_irc40k(
+    rcu: &mut Rcu,
+    afio: &mut Afio,
+    timer: &mut TIMER4,
+    timeout: u64,
+) -> Result<u32, CalibrationError> {
+    let irc40k_enabled = rcu.regs.rstsck.read().irc40ken().bit_is_set();
+    rcu.regs.rstsck.modify(|_, w| w.irc40ken().set_bit());
+    let stable = wait_until(timeout, || rcu.regs.rstsck.read().irc40kstb().bit_is_set());
+
+    let ticks = if stable {
+        TIMER4::enable(rcu);
+        TIMER4::reset(rcu);
+
+        // Connect IRC40K to channel 3
+        afio.set_timer4ch3_iremap(true);
+
+        // Free-running counter, channel 3 captures every 8th rising edge of CI3
+        timer.psc.write(|w| unsafe { w.psc().bits(0) });
+        timer.car.write(|w| unsafe { w.carl().bits(0xFFFF) });
+        timer.chctl1_input_mut().modify(|_, w| unsafe { w
+            .ch3ms().bits(0b01)
+            .ch3capflt().bits(0)
+            .ch3cappsc().bits(0b11)
+        });
+        timer.chctl2.modify(|_, w| w.ch3en().set_bit());
+        timer.ctl0.modify(|_, w| w.cen().set_bit());
+
+        let ticks = interrupt::free(|_| {
+            let capture = || {
+                if !wait_until(timeout, || timer.intf.read().ch3if().bit_is_set()) {
+                    return Err(CalibrationError::Irc40kTimeout);
+                }
+                // Reading CH3CV clears CH3IF
+                Ok(timer.ch3cv.read().ch3val().bits())
+            };
+
+            let mut last = capture()?;
+            let mut ticks = 0;
+            for _ in 0..IRC40K_CAPTURES {
+                let next = capture()?;
+                ticks += u32::from(next.wrapping_sub(last));
+                last = next;
+            }
+            Ok(ticks)
+        });
+
+        timer.ctl0.modify(|_, w| w.cen().clear_bit());
+        timer.chctl2.modify(|_, w| w.ch3en().clear_bit());
+        afio.set_timer4ch3_iremap(false);
+        TIMER4::disable(rcu);
+        ticks
+    } else {
+        Err(CalibrationError::Irc40kTimeout)
+    };
+
+    if !irc40k_enabled {
+        rcu.regs.rstsck.modify(|_, w| w.irc40ken().clear_bit());
+    }
+
+    ticks
+}
@@ -19,7 +19,7 @@ impl McycleDelay {
     /// Constructs the delay provider
     pub fn new(clocks: &Clocks) -> Self {
         Self {
-            core_frequency: clocks.sysclk().0
+            core_frequency: clocks.hclk().0
         }
     }
 }
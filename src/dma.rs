@@ -3,6 +3,7 @@
 
 use core::{
     marker::PhantomData,
+    mem, ptr,
     sync::atomic::{compiler_fence, Ordering},
 };
 use embedded_dma::{StaticReadBuffer, StaticWriteBuffer};
@@ -32,6 +33,128 @@ pub enum Direction {
     MemoryToMemory,
 }
 
+/// Size of the words moved by a DMA transfer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WordWidth {
+    Bits8 = 0b00,
+    Bits16 = 0b01,
+    Bits32 = 0b10,
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Word types DMA transfers can move
+pub trait Word: sealed::Sealed + Copy {
+    const WIDTH: WordWidth;
+}
+
+macro_rules! word {
+    ($($T:ty: $width:ident,)+) => {
+        $(
+            impl sealed::Sealed for $T {}
+            impl Word for $T {
+                const WIDTH: WordWidth = WordWidth::$width;
+            }
+        )+
+    };
+}
+
+word! {
+    u8: Bits8,
+    i8: Bits8,
+    u16: Bits16,
+    i16: Bits16,
+    u32: Bits32,
+    i32: Bits32,
+}
+
+/// Channel priority level
+///
+/// Requests of channels with the same priority are served in channel number order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    Low = 0b00,
+    Medium = 0b01,
+    High = 0b10,
+    UltraHigh = 0b11,
+}
+
+/// DMA channel configuration
+///
+/// Defaults to the reset state: low priority, 8-bit words, fixed addresses and no circular mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChannelConfig {
+    priority: Priority,
+    peripheral_width: WordWidth,
+    memory_width: WordWidth,
+    peripheral_increment: bool,
+    memory_increment: bool,
+    circular: bool,
+}
+
+impl ChannelConfig {
+    /// Sets the channel priority level
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Sets the size of the words read from or written to the peripheral (PWIDTH)
+    pub fn peripheral_width(mut self, width: WordWidth) -> Self {
+        self.peripheral_width = width;
+        self
+    }
+
+    /// Sets the size of the words read from or written to memory (MWIDTH)
+    pub fn memory_width(mut self, width: WordWidth) -> Self {
+        self.memory_width = width;
+        self
+    }
+
+    /// Sets the peripheral and memory word sizes from the `WORD` type
+    pub fn word<WORD: Word>(self) -> Self {
+        self.peripheral_width(WORD::WIDTH).memory_width(WORD::WIDTH)
+    }
+
+    /// Increments the peripheral address after every word (PNAGA)
+    pub fn peripheral_increment(mut self, increment: bool) -> Self {
+        self.peripheral_increment = increment;
+        self
+    }
+
+    /// Increments the memory address after every word (MNAGA)
+    pub fn memory_increment(mut self, increment: bool) -> Self {
+        self.memory_increment = increment;
+        self
+    }
+
+    /// Restarts the transfer from the initial addresses and count once it completes (CMEN)
+    pub fn circular(mut self, circular: bool) -> Self {
+        self.circular = circular;
+        self
+    }
+}
+
+impl Default for ChannelConfig {
+    fn default() -> ChannelConfig {
+        ChannelConfig {
+            priority: Priority::Low,
+            peripheral_width: WordWidth::Bits8,
+            memory_width: WordWidth::Bits8,
+            peripheral_increment: false,
+            memory_increment: false,
+            circular: false,
+        }
+    }
+}
+
+/// Memory-to-memory transfer on a DMA channel
+pub struct MemToMem<CHANNEL> {
+    pub(crate) channel: CHANNEL,
+}
+
 pub struct CircBuffer<BUFFER, PAYLOAD>
 where
     BUFFER: 'static,
@@ -101,6 +224,27 @@ where
     }
 }
 
+impl<MODE, BUFFER, PAYLOAD> Transfer<MODE, BUFFER, PAYLOAD>
+where
+    PAYLOAD: TransferPayload,
+{
+    pub(crate) fn payload(&self) -> &PAYLOAD {
+        &self.payload
+    }
+
+    /// Takes the buffer and payload back without stopping the payload
+    pub(crate) fn into_parts(self) -> (BUFFER, PAYLOAD) {
+        // NOTE(unsafe) There is no panic branch between getting the resources
+        // and forgetting `self`.
+        unsafe {
+            let buffer = ptr::read(&self.buffer);
+            let payload = ptr::read(&self.payload);
+            mem::forget(self);
+            (buffer, payload)
+        }
+    }
+}
+
 impl<MODE, BUFFER, PAYLOAD> Drop for Transfer<MODE, BUFFER, PAYLOAD>
 where
     PAYLOAD: TransferPayload,
@@ -142,7 +286,11 @@ macro_rules! dma {
                 use crate::pac::{$DMAX, $dmaX};
                 use crate::rcu::{Rcu, Enable};
 
-                use crate::dma::{CircBuffer, Direction, DmaExt, Error, Event, Half, Transfer, W, RxDma, TxDma, TransferPayload};
+                use crate::dma::{
+                    ChannelConfig, CircBuffer, Direction, DmaExt, Error, Event, Half, MemToMem, Priority,
+                    Transfer, R, W, RxDma, RxTxDma, TxDma, TransferPayload, Word,
+                };
+                use embedded_dma::{StaticReadBuffer, StaticWriteBuffer};
 
                 #[allow(clippy::manual_non_exhaustive)]
                 pub struct Channels((), $(pub $CX),+);
@@ -191,6 +339,22 @@ macro_rules! dma {
                             };
                         }
 
+                        /// Applies the priority, word sizes, address increments and circular mode
+                        /// of `config`
+                        ///
+                        /// The channel must be stopped, these settings can't be changed while a
+                        /// transfer is in progress.
+                        pub fn configure(&mut self, config: ChannelConfig) {
+                            self.ctl().modify(|_, w| unsafe { w
+                                .prio().bits(config.priority as u8)
+                                .pwidth().bits(config.peripheral_width as u8)
+                                .mwidth().bits(config.memory_width as u8)
+                                .pnaga().bit(config.peripheral_increment)
+                                .mnaga().bit(config.memory_increment)
+                                .cmen().bit(config.circular)
+                            });
+                        }
+
                         /// Starts the DMA transfer
                         pub fn start(&mut self) {
                             self.ctl().modify(|_, w| w.chen().set_bit() );
@@ -255,6 +419,134 @@ macro_rules! dma {
                         }
                     }
 
+                    impl $CX {
+                        /// Copies `src` into `dst`
+                        ///
+                        /// Panics if the buffers differ in length or are empty.
+                        pub fn mem2mem<WORD, S, D>(mut self, src: S, mut dst: D) -> Transfer<W, (S, D), MemToMem<$CX>>
+                        where
+                            WORD: Word,
+                            S: StaticReadBuffer<Word = WORD>,
+                            D: StaticWriteBuffer<Word = WORD>,
+                        {
+                            // NOTE(unsafe) We own the buffers now and we won't call other `&mut`
+                            // on them until the end of the transfer.
+                            let (src_ptr, src_len) = unsafe { src.static_read_buffer() };
+                            let (dst_ptr, dst_len) = unsafe { dst.static_write_buffer() };
+                            assert_eq!(src_len, dst_len, "buffers must have the same length");
+                            assert!(dst_len > 0, "empty DMA transfer");
+
+                            self.setup_mem2mem::<WORD>(src_ptr as u32, true, dst_ptr as u32, true, dst_len);
+                            self.start();
+
+                            Transfer::w((src, dst), MemToMem { channel: self })
+                        }
+
+                        /// Fills `dst` with `value`
+                        ///
+                        /// The first word is written by the CPU and copied to the rest of the
+                        /// buffer. Panics if `dst` is empty.
+                        pub fn mem2mem_fill<WORD, D>(mut self, value: WORD, mut dst: D) -> Transfer<W, D, MemToMem<$CX>>
+                        where
+                            WORD: Word,
+                            D: StaticWriteBuffer<Word = WORD>,
+                        {
+                            // NOTE(unsafe) We own the buffer now and we won't call other `&mut`
+                            // on it until the end of the transfer.
+                            let (dst_ptr, dst_len) = unsafe { dst.static_write_buffer() };
+                            assert!(dst_len > 0, "empty DMA transfer");
+
+                            // NOTE(unsafe) `dst_ptr` points to at least one word
+                            unsafe { ptr::write_volatile(dst_ptr, value) };
+                            self.setup_mem2mem::<WORD>(dst_ptr as u32, false, dst_ptr as u32, true, dst_len);
+                            self.start();
+
+                            Transfer::w(dst, MemToMem { channel: self })
+                        }
+
+                        /// Copies `src` word by word to the fixed address `dst`
+                        ///
+                        /// Meant for memory-mapped data registers, like the data port of an
+                        /// EXMC-attached LCD. Panics if `src` is empty.
+                        ///
+                        /// # Safety
+                        ///
+                        /// `dst` must stay valid for `WORD` writes until the transfer ends.
+                        pub unsafe fn mem2mem_to_address<WORD, S>(mut self, src: S, dst: *mut WORD) -> Transfer<R, S, MemToMem<$CX>>
+                        where
+                            WORD: Word,
+                            S: StaticReadBuffer<Word = WORD>,
+                        {
+                            // NOTE(unsafe) We own the buffer now and we won't call other `&mut`
+                            // on it until the end of the transfer.
+                            let (src_ptr, src_len) = unsafe { src.static_read_buffer() };
+                            assert!(src_len > 0, "empty DMA transfer");
+
+                            self.setup_mem2mem::<WORD>(src_ptr as u32, true, dst as u32, false, src_len);
+                            self.start();
+
+                            Transfer::r(src, MemToMem { channel: self })
+                        }
+
+                        fn setup_mem2mem<WORD: Word>(&mut self, src: u32, src_inc: bool, dst: u32, dst_inc: bool, len: usize) {
+                            // The source is accessed through the peripheral address
+                            unsafe {
+                                self.set_peripheral_address(src, src_inc);
+                                self.set_memory_address(dst, dst_inc);
+                            }
+                            self.set_transfer_length(len);
+
+                            atomic::compiler_fence(Ordering::Release);
+                            self.configure(ChannelConfig::default()
+                                .priority(Priority::Medium)
+                                .word::<WORD>()
+                                .peripheral_increment(src_inc)
+                                .memory_increment(dst_inc)
+                            );
+                            self.ctl().modify(|_, w| w.m2m().set_bit().dir().clear_bit());
+                        }
+                    }
+
+                    impl TransferPayload for MemToMem<$CX> {
+                        fn start(&mut self) {
+                            self.channel.start();
+                        }
+                        fn stop(&mut self) {
+                            self.channel.stop();
+                        }
+                    }
+
+                    impl<MODE, BUFFER> Transfer<MODE, BUFFER, MemToMem<$CX>> {
+                        pub fn is_done(&self) -> bool {
+                            !self.payload.channel.in_progress()
+                        }
+
+                        /// Waits for the transfer to end and returns the buffers and the channel
+                        pub fn wait(mut self) -> (BUFFER, $CX) {
+                            while !self.is_done() {}
+
+                            atomic::compiler_fence(Ordering::Acquire);
+
+                            self.payload.stop();
+
+                            // we need a read here to make the Acquire fence effective
+                            // we do *not* need this if `dma.stop` does a RMW operation
+                            unsafe { ptr::read_volatile(&0); }
+
+                            // we need a fence here for the same reason we need one in `Transfer.wait`
+                            atomic::compiler_fence(Ordering::Acquire);
+
+                            // NOTE(unsafe) There is no panic branch between getting the resources
+                            // and forgetting `self`.
+                            unsafe {
+                                let buffer = ptr::read(&self.buffer);
+                                let payload = ptr::read(&self.payload);
+                                mem::forget(self);
+                                (buffer, payload.channel)
+                            }
+                        }
+                    }
+
                     impl<B, PAYLOAD> CircBuffer<B, RxDma<PAYLOAD, $CX>>
                     where
                         RxDma<PAYLOAD, $CX>: TransferPayload,
@@ -407,6 +699,40 @@ macro_rules! dma {
                         }
                     }
 
+                    impl<BUFFER, PAYLOAD, MODE, TXC> Transfer<MODE, BUFFER, RxTxDma<PAYLOAD, $CX, TXC>>
+                    where
+                        RxTxDma<PAYLOAD, $CX, TXC>: TransferPayload,
+                    {
+                        /// Returns `true` once the last word has been received
+                        pub fn is_done(&self) -> bool {
+                            !self.payload.rxchannel.in_progress()
+                        }
+
+                        pub fn wait(mut self) -> (BUFFER, RxTxDma<PAYLOAD, $CX, TXC>) {
+                            while !self.is_done() {}
+
+                            atomic::compiler_fence(Ordering::Acquire);
+
+                            self.payload.stop();
+
+                            // we need a read here to make the Acquire fence effective
+                            // we do *not* need this if `dma.stop` does a RMW operation
+                            unsafe { ptr::read_volatile(&0); }
+
+                            // we need a fence here for the same reason we need one in `Transfer.wait`
+                            atomic::compiler_fence(Ordering::Acquire);
+
+                            // NOTE(unsafe) There is no panic branch between getting the resources
+                            // and forgetting `self`.
+                            unsafe {
+                                let buffer = ptr::read(&self.buffer);
+                                let payload = ptr::read(&self.payload);
+                                mem::forget(self);
+                                (buffer, payload)
+                            }
+                        }
+                    }
+
                     impl<BUFFER, PAYLOAD> Transfer<W, BUFFER, RxDma<PAYLOAD, $CX>>
                     where
                         RxDma<PAYLOAD, $CX>: TransferPayload,
@@ -580,3 +906,16 @@ where
 {
     fn write(self, buffer: B) -> Transfer<R, B, Self>;
 }
+
+/// Trait for simultaneous DMA reading and writing, e.g. full-duplex SPI.
+pub trait ReadWriteDma<RXB, TXB, TS>: Transmit
+where
+    RXB: StaticWriteBuffer<Word = TS>,
+    TXB: StaticReadBuffer<Word = TS>,
+    Self: core::marker::Sized + TransferPayload,
+{
+    /// Receives into `rx_buffer` while transmitting `tx_buffer`
+    ///
+    /// Panics if the buffers differ in length.
+    fn read_write(self, rx_buffer: RXB, tx_buffer: TXB) -> Transfer<W, (RXB, TXB), Self>;
+}
@@ -1,8 +1,10 @@
 use crate::pac::ECLIC;
+use core::sync::atomic::{compiler_fence, Ordering};
 use riscv::interrupt::Nr;
 
 /// The code is based on vendor provided HAL libraries.
 /// Most code come from Firmware\RISCV\env_Eclipse\start.S
+#[cfg(target_arch = "riscv32")]
 pub mod mode;
 
 const EFFECTIVE_LEVEL_PRIORITY_BITS: u8 = 4;
@@ -25,6 +27,93 @@ pub enum TriggerType {
     FallingEdge = 3,
 }
 
+/// How the ECLIC enters the handler of an interrupt
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VectorMode {
+    /// Through the common `_irq_handler` trampoline, which saves the context and calls the
+    /// handler as a normal function
+    NonVectored = 0,
+    /// Directly from the vector table (selective hardware vectoring). The handler must be
+    /// defined with [`vectored_interrupt!`](crate::vectored_interrupt)
+    Vectored = 1,
+}
+
+/// Defines `$name` as a hardware vectored entry stub calling `$handler`
+///
+/// The stub saves the caller-saved registers, calls `$handler` with interrupts disabled,
+/// restores the registers and returns with `mret`. Use it instead of defining the handler
+/// function itself, and set the interrupt up with [`VectorMode::Vectored`]. Handlers defined
+/// this way must not be used in non-vectored mode.
+///
+/// ```no_run
+/// use gd32vf103xx_hal::vectored_interrupt;
+///
+/// fn timer0_update() {
+///     // Motor control loop
+/// }
+///
+/// vectored_interrupt!(TIMER0_UP, timer0_update);
+/// ```
+#[macro_export]
+macro_rules! vectored_interrupt {
+    ($name:ident, $handler:path) => {
+        #[allow(non_snake_case)]
+        mod $name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            extern "C" fn handler() {
+                $handler()
+            }
+
+            core::arch::global_asm!(
+                concat!(".section .text.vectored.", stringify!($name), ", \"ax\""),
+                ".align 2",
+                concat!(".global ", stringify!($name)),
+                concat!(stringify!($name), ":"),
+                "addi sp, sp, -16*4",
+                "sw ra, 0*4(sp)",
+                "sw t0, 1*4(sp)",
+                "sw t1, 2*4(sp)",
+                "sw t2, 3*4(sp)",
+                "sw t3, 4*4(sp)",
+                "sw t4, 5*4(sp)",
+                "sw t5, 6*4(sp)",
+                "sw t6, 7*4(sp)",
+                "sw a0, 8*4(sp)",
+                "sw a1, 9*4(sp)",
+                "sw a2, 10*4(sp)",
+                "sw a3, 11*4(sp)",
+                "sw a4, 12*4(sp)",
+                "sw a5, 13*4(sp)",
+                "sw a6, 14*4(sp)",
+                "sw a7, 15*4(sp)",
+                "call {handler}",
+                "lw ra, 0*4(sp)",
+                "lw t0, 1*4(sp)",
+                "lw t1, 2*4(sp)",
+                "lw t2, 3*4(sp)",
+                "lw t3, 4*4(sp)",
+                "lw t4, 5*4(sp)",
+                "lw t5, 6*4(sp)",
+                "lw t6, 7*4(sp)",
+                "lw a0, 8*4(sp)",
+                "lw a1, 9*4(sp)",
+                "lw a2, 10*4(sp)",
+                "lw a3, 11*4(sp)",
+                "lw a4, 12*4(sp)",
+                "lw a5, 13*4(sp)",
+                "lw a6, 14*4(sp)",
+                "lw a7, 15*4(sp)",
+                "addi sp, sp, 16*4",
+                "mret",
+                handler = sym handler,
+            );
+        }
+    };
+}
+
 #[repr(u8)]
 #[derive(Debug)]
 pub enum Level {
@@ -67,6 +156,61 @@ pub enum Priority {
     P15 = 15,
 }
 
+/// Runs `f` with the interrupts at or below `level` blocked
+///
+/// Raises the ECLIC threshold (`mth`) to `level` for the duration of `f` and restores the
+/// previous threshold afterwards. Interrupts above `level` keep preempting `f`, unlike with
+/// `riscv::interrupt::free`. The threshold is never lowered, so nested locks are cheap.
+pub fn lock<R>(level: Level, f: impl FnOnce() -> R) -> R {
+    let eclic = unsafe { &*ECLIC::ptr() };
+
+    // Levels are left-justified in the 8 bit threshold, with the unused bits read as ones
+    let level_bits = ECLIC::get_level_bits();
+    let level = core::cmp::min(level as u8, ((1u16 << level_bits) - 1) as u8);
+    let unused = ((1u16 << (8 - level_bits)) - 1) as u8;
+    let threshold = (u16::from(level) << (8 - level_bits)) as u8 | unused;
+
+    let previous = eclic.mth.read().mth().bits();
+    if threshold > previous {
+        eclic.mth.write(|w| unsafe { w.mth().bits(threshold) });
+    }
+    // Keep the accesses made by `f` between the threshold writes
+    compiler_fence(Ordering::SeqCst);
+
+    let r = f();
+
+    compiler_fence(Ordering::SeqCst);
+    eclic.mth.write(|w| unsafe { w.mth().bits(previous) });
+    r
+}
+
+#[cfg(feature = "critical-section-single-hart")]
+mod single_hart {
+    use core::sync::atomic::{compiler_fence, Ordering};
+    use riscv::interrupt;
+    use riscv::register::mstatus;
+
+    struct SingleHartCriticalSection;
+    critical_section::set_impl!(SingleHartCriticalSection);
+
+    unsafe impl critical_section::Impl for SingleHartCriticalSection {
+        unsafe fn acquire() -> critical_section::RawRestoreState {
+            let was_enabled = mstatus::read().mie();
+            unsafe { interrupt::disable() };
+            compiler_fence(Ordering::SeqCst);
+            was_enabled
+        }
+
+        unsafe fn release(was_enabled: critical_section::RawRestoreState) {
+            compiler_fence(Ordering::SeqCst);
+            // Only re-enable interrupts if they were enabled before the critical section
+            if was_enabled {
+                unsafe { interrupt::enable() };
+            }
+        }
+    }
+}
+
 pub trait EclicExt {
     /// Reset all ECLIC registers to 0
     fn reset();
@@ -88,7 +232,13 @@ pub trait EclicExt {
     fn get_priority_bits() -> u8;
 
     /// Setup `interrupt`
-    fn setup<I: Nr + Copy>(interrupt: I, tt: TriggerType, level: Level, priority: Priority);
+    fn setup<I: Nr + Copy>(
+        interrupt: I,
+        tt: TriggerType,
+        level: Level,
+        priority: Priority,
+        mode: VectorMode,
+    );
 
     /// Enables `interrupt`
     unsafe fn unmask<I: Nr>(interrupt: I);
@@ -114,6 +264,12 @@ pub trait EclicExt {
     /// Get `interrupt` trigger type
     fn get_trigger_type<I: Nr>(interrupt: I) -> Option<TriggerType>;
 
+    /// Set `interrupt` vectoring mode
+    fn set_vector_mode<I: Nr>(interrupt: I, mode: VectorMode);
+
+    /// Get `interrupt` vectoring mode
+    fn get_vector_mode<I: Nr>(interrupt: I) -> VectorMode;
+
     // Set `interrupt` level
     fn set_level<I: Nr>(interrupt: I, level: Level);
 
@@ -185,9 +341,16 @@ impl EclicExt for ECLIC {
         EFFECTIVE_LEVEL_PRIORITY_BITS - Self::get_level_bits()
     }
 
-    fn setup<I: Nr + Copy>(interrupt: I, tt: TriggerType, level: Level, priority: Priority) {
+    fn setup<I: Nr + Copy>(
+        interrupt: I,
+        tt: TriggerType,
+        level: Level,
+        priority: Priority,
+        mode: VectorMode,
+    ) {
         Self::mask(interrupt);
         Self::set_trigger_type(interrupt, tt);
+        Self::set_vector_mode(interrupt, mode);
         Self::set_level(interrupt, level);
         Self::set_priority(interrupt, priority);
         Self::unpend(interrupt);
@@ -268,7 +431,29 @@ impl EclicExt for ECLIC {
         unsafe {
             (*Self::ptr()).clicints[nr]
                 .clicintattr
-                .write(|w| w.trig().bits(tt as u8).shv().clear_bit())
+                .modify(|_, w| w.trig().bits(tt as u8))
+        }
+    }
+
+    #[inline]
+    fn set_vector_mode<I: Nr>(interrupt: I, mode: VectorMode) {
+        let nr = usize::from(interrupt.nr());
+
+        unsafe {
+            (*Self::ptr()).clicints[nr]
+                .clicintattr
+                .modify(|_, w| w.shv().bit(mode == VectorMode::Vectored))
+        }
+    }
+
+    #[inline]
+    fn get_vector_mode<I: Nr>(interrupt: I) -> VectorMode {
+        let nr = usize::from(interrupt.nr());
+
+        if unsafe { (*Self::ptr()).clicints[nr].clicintattr.read().shv().bit_is_set() } {
+            VectorMode::Vectored
+        } else {
+            VectorMode::NonVectored
         }
     }
 
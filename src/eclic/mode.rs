@@ -123,7 +123,7 @@ global_asm!(
         "sw a7, 15*REGBYTES(sp)",
         //
         "add a0, sp, zero",
-        "jal ra, _start_trap_rust",
+        "jal ra, _start_trap_hal",
         //
         "lw ra, 0*REGBYTES(sp)",
         "lw t0, 1*REGBYTES(sp)",
@@ -1,6 +1,9 @@
 //! External interrupt controller (EXTI).
 
+use crate::afio::Afio;
 use crate::pac::EXTI;
+use core::cell::Cell;
+use riscv::interrupt::{self, Mutex};
 
 /// An `ExtiLine` that can be `listen()`ed for interrupt
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -59,6 +62,53 @@ pub enum TriggerEdge {
     Both,
 }
 
+/// Error returned when an EXTI line is already routed to a pin of another port
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LineInUse;
+
+/// GPIO pin usable as an EXTI interrupt source
+///
+/// Replaces the separate `Afio::extiss`, `ExtiLine::from_gpio_line` and `Exti::listen` calls.
+pub trait ExtiPin {
+    /// Returns the EXTI line of the pin
+    fn exti_line(&self) -> ExtiLine;
+
+    /// Routes the EXTI line to this pin
+    ///
+    /// Fails if a pin of another port already claimed the line.
+    fn make_interrupt_source(&mut self, afio: &mut Afio) -> Result<(), LineInUse>;
+
+    /// Gives the EXTI line back so that a pin of another port can claim it
+    ///
+    /// Disables the interrupt of the line. Does nothing if the line isn't claimed by this pin's port.
+    fn release_interrupt_source(&mut self, afio: &mut Afio, exti: &mut Exti);
+
+    /// Selects the edges that trigger the interrupt
+    fn trigger_on_edge(&mut self, exti: &mut Exti, edge: TriggerEdge) {
+        exti.set_trigger_edge(self.exti_line(), edge);
+    }
+
+    /// Enables the interrupt of the EXTI line
+    fn enable_interrupt(&mut self, exti: &mut Exti) {
+        exti.enable_interrupt(self.exti_line());
+    }
+
+    /// Disables the interrupt of the EXTI line
+    fn disable_interrupt(&mut self, exti: &mut Exti) {
+        exti.disable_interrupt(self.exti_line());
+    }
+
+    /// Returns `true` if the EXTI line has a pending interrupt
+    fn check_interrupt(&self) -> bool {
+        Exti::is_pending(self.exti_line())
+    }
+
+    /// Clears the pending interrupt flag of the EXTI line
+    fn clear_interrupt_pending_bit(&mut self) {
+        Exti::clear(self.exti_line());
+    }
+}
+
 /// Higher-lever wrapper around the `EXTI` peripheral.
 pub struct Exti {
     raw: EXTI,
@@ -78,6 +128,13 @@ impl Exti {
     /// Listen on one of the Lines
     #[inline]
     pub fn listen(&mut self, line: ExtiLine, edge: TriggerEdge) {
+        self.set_trigger_edge(line, edge);
+        self.enable_interrupt(line);
+    }
+
+    /// Selects the edges that trigger the line, without enabling its interrupt
+    #[inline]
+    pub fn set_trigger_edge(&mut self, line: ExtiLine, edge: TriggerEdge) {
         let bm: u32 = 1 << line.0;
 
         unsafe {
@@ -95,11 +152,23 @@ impl Exti {
                     self.raw.rten.modify(|r, w| w.bits(r.bits() | bm));
                 }
             }
-
-            self.raw.inten.modify(|r, w| w.bits(r.bits() | bm));
         }
     }
 
+    /// Enables the interrupt of the line
+    #[inline]
+    pub fn enable_interrupt(&mut self, line: ExtiLine) {
+        let bm: u32 = 1 << line.0;
+        unsafe { self.raw.inten.modify(|r, w| w.bits(r.bits() | bm)) };
+    }
+
+    /// Disables the interrupt of the line, keeping its trigger edges
+    #[inline]
+    pub fn disable_interrupt(&mut self, line: ExtiLine) {
+        let bm: u32 = 1 << line.0;
+        unsafe { self.raw.inten.modify(|r, w| w.bits(r.bits() & !bm)) };
+    }
+
     /// Unlisten on the specified line
     #[inline]
     pub fn unlisten(&mut self, line: ExtiLine) {
@@ -152,3 +221,97 @@ impl Exti {
         unsafe { (*EXTI::ptr()).swiev.modify(|r, w| w.bits(r.bits() & !bm)) };
     }
 }
+
+/// Handler of an EXTI line, called with the line that triggered
+pub type ExtiHandler = fn(ExtiLine);
+
+/// Per-line handler table for the EXTI interrupt vectors
+///
+/// Lines 5..=9 share `EXTI_LINE9_5` and lines 10..=15 share `EXTI_LINE15_10`. The dispatcher
+/// checks which of the lines are pending, clears them and calls the registered handlers.
+///
+/// ```no_run
+/// use gd32vf103xx_hal::exti::{ExtiDispatcher, ExtiLine};
+///
+/// static EXTI_HANDLERS: ExtiDispatcher = ExtiDispatcher::new();
+///
+/// fn on_button(_line: ExtiLine) {}
+///
+/// #[allow(non_snake_case)]
+/// #[unsafe(no_mangle)]
+/// fn EXTI_LINE9_5() {
+///     EXTI_HANDLERS.dispatch_line9_5();
+/// }
+///
+/// # fn main() {
+/// EXTI_HANDLERS.register(ExtiLine::from_gpio_line(7).unwrap(), on_button);
+/// # }
+/// ```
+pub struct ExtiDispatcher {
+    handlers: Mutex<Cell<[Option<ExtiHandler>; 16]>>,
+}
+
+impl ExtiDispatcher {
+    /// Creates a dispatcher without handlers
+    pub const fn new() -> Self {
+        Self {
+            handlers: Mutex::new(Cell::new([None; 16])),
+        }
+    }
+
+    /// Registers `handler` for a GPIO line, replacing the previous handler
+    pub fn register(&self, line: ExtiLine, handler: ExtiHandler) {
+        self.set(line, Some(handler));
+    }
+
+    /// Removes the handler of a GPIO line
+    pub fn unregister(&self, line: ExtiLine) {
+        self.set(line, None);
+    }
+
+    fn set(&self, line: ExtiLine, handler: Option<ExtiHandler>) {
+        assert!(line.0 < 16, "only GPIO lines can be dispatched");
+
+        interrupt::free(|cs| {
+            let cell = self.handlers.borrow(cs);
+            let mut handlers = cell.get();
+            handlers[line.0 as usize] = handler;
+            cell.set(handlers);
+        });
+    }
+
+    /// Handles the pending lines selected by `mask`
+    ///
+    /// Every line that has its interrupt enabled and is pending is cleared, then its handler is
+    /// called. Pending lines without a handler are cleared as well.
+    pub fn dispatch(&self, mask: u16) {
+        let handlers = interrupt::free(|cs| self.handlers.borrow(cs).get());
+        let enabled = unsafe { (*EXTI::ptr()).inten.read().bits() };
+
+        for i in (0..16).filter(|i| mask & (1 << i) != 0 && enabled & (1 << i) != 0) {
+            let line = ExtiLine(i);
+            if Exti::is_pending(line) {
+                Exti::clear(line);
+                if let Some(handler) = handlers[i as usize] {
+                    handler(line);
+                }
+            }
+        }
+    }
+
+    /// Handles the lines 5..=9 of the `EXTI_LINE9_5` vector
+    pub fn dispatch_line9_5(&self) {
+        self.dispatch(0b11111 << 5);
+    }
+
+    /// Handles the lines 10..=15 of the `EXTI_LINE15_10` vector
+    pub fn dispatch_line15_10(&self) {
+        self.dispatch(0b111111 << 10);
+    }
+}
+
+impl Default for ExtiDispatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
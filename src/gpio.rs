@@ -13,6 +13,7 @@ pub trait GpioExt {
     fn split(self, rcu: &mut Rcu) -> Self::Parts;
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Port {
     PAx = 0,
@@ -62,11 +63,97 @@ pub struct Alternate<MODE> {
 }
 impl<MODE> Active for Alternate<MODE> {}
 
+/// Input or output mode selected at runtime (type state)
+///
+/// The pin starts as a floating input and is reconfigured with the `make_*` methods. Reading
+/// the pin is allowed while it is an input or an open-drain output, driving it only while it is
+/// an output.
+pub struct Dynamic;
+impl Active for Dynamic {}
+
+/// Pin whose configuration is locked until the next reset (type state)
+///
+/// Created by `lock` on a single pin or by the port's `lock_pins` function. Locked pins keep
+/// their input/output behaviour but none of the mode changing methods.
+pub struct Locked<MODE> {
+    _mode: PhantomData<MODE>,
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Pins of the port `PORT` whose configuration can be locked at once
+///
+/// Implemented for single pins and for tuples of up to 8 pins of the same port.
+pub trait LockablePins<PORT>: sealed::Sealed {
+    /// The pins with locked configuration
+    type Locked;
+
+    #[doc(hidden)]
+    fn mask(&self) -> u16;
+
+    /// # Safety
+    ///
+    /// The configuration of the pins must be locked
+    #[doc(hidden)]
+    unsafe fn into_locked(self) -> Self::Locked;
+}
+
+macro_rules! lockable_tuple {
+    ($($T:ident: $t:ident),+) => {
+        impl<$($T: sealed::Sealed),+> sealed::Sealed for ($($T,)+) {}
+
+        impl<PORT, $($T: LockablePins<PORT>),+> LockablePins<PORT> for ($($T,)+) {
+            type Locked = ($($T::Locked,)+);
+
+            fn mask(&self) -> u16 {
+                let ($($t,)+) = self;
+                0 $(| $t.mask())+
+            }
+
+            unsafe fn into_locked(self) -> Self::Locked {
+                let ($($t,)+) = self;
+                // NOTE(unsafe) forwarded from the caller
+                unsafe { ($($t.into_locked(),)+) }
+            }
+        }
+    }
+}
+
+lockable_tuple!(A: a);
+lockable_tuple!(A: a, B: b);
+lockable_tuple!(A: a, B: b, C: c);
+lockable_tuple!(A: a, B: b, C: c, D: d);
+lockable_tuple!(A: a, B: b, C: c, D: d, E: e);
+lockable_tuple!(A: a, B: b, C: c, D: d, E: e, F: f);
+lockable_tuple!(A: a, B: b, C: c, D: d, E: e, F: f, G: g);
+lockable_tuple!(A: a, B: b, C: c, D: d, E: e, F: f, G: g, H: h);
+
+/// Error returned when a `Dynamic` pin is used in a way its current mode doesn't allow
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PinModeError {
+    /// The pin is not configured for this operation
+    IncorrectMode,
+}
+
 pub enum State {
     High,
     Low,
 }
 
+/// Output slew rate (maximum output frequency)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Speed {
+    /// Up to 10 MHz
+    Mhz10 = 0b01,
+    /// Up to 2 MHz, slowest edges
+    Mhz2 = 0b10,
+    /// Up to 50 MHz, fastest edges (default)
+    Mhz50 = 0b11,
+}
+
 #[derive(Copy, Clone)]
 #[repr(u8)]
 enum InputPortConfiguration {
@@ -84,23 +171,18 @@ enum OutputPortConfiguration {
     AfioOpenDrain = 0b1100,
 }
 
-#[allow(dead_code)]
 #[derive(Copy, Clone)]
 enum PortMode {
     Input(InputPortConfiguration),
-    Output10Mhz(OutputPortConfiguration),
-    Output2Mhz(OutputPortConfiguration),
-    Output50Mhz(OutputPortConfiguration),
+    Output(OutputPortConfiguration, Speed),
 }
 
 impl PortMode {
     #[inline(always)]
     pub fn into_bits(self) -> u8 {
         match self {
-            PortMode::Input(conf)       => (conf as u8) | 0b00,
-            PortMode::Output10Mhz(conf) => (conf as u8) | 0b01,
-            PortMode::Output2Mhz(conf)  => (conf as u8) | 0b10,
-            PortMode::Output50Mhz(conf) => (conf as u8) | 0b11,
+            PortMode::Input(conf)         => (conf as u8) | 0b00,
+            PortMode::Output(conf, speed) => (conf as u8) | (speed as u8),
         }
     }
 }
@@ -109,11 +191,58 @@ trait PeripheralAccess {
     fn peripheral() -> &'static crate::pac::gpioa::RegisterBlock;
 
     fn set_mode(index: u8, mode: PortMode) {
+        Self::modify_ctl(index, 0b1111, mode.into_bits());
+    }
+
+    /// Changes the MD bits only, keeping the output configuration
+    fn set_speed(index: u8, speed: Speed) {
+        Self::modify_ctl(index, 0b0011, speed as u8);
+    }
+
+    /// Returns the CTL and MD bits of the pin
+    fn ctl_bits(index: u8) -> u8 {
         assert!(index < 16);
 
-        let bits = mode.into_bits();
         let offset = (index * 4) % 32;
-        let mask = !(0b1111u32 << offset);
+        let regs = Self::peripheral();
+        let ctl = if index < 8 {
+            regs.ctl0.read().bits()
+        } else {
+            regs.ctl1.read().bits()
+        };
+        ((ctl >> offset) & 0b1111) as u8
+    }
+
+    /// Returns the pin configuration, including the OCTL bit which selects the pull direction
+    fn save_config(index: u8) -> (u8, bool) {
+        (Self::ctl_bits(index), Self::is_set_high(index))
+    }
+
+    fn restore_config(index: u8, (bits, octl): (u8, bool)) {
+        if octl {
+            Self::set_bit(index);
+        } else {
+            Self::clear_bit(index);
+        }
+        Self::modify_ctl(index, 0b1111, bits);
+    }
+
+    /// Returns `true` if the MD bits select an output mode
+    fn is_output(index: u8) -> bool {
+        Self::ctl_bits(index) & 0b11 != 0
+    }
+
+    /// Returns `true` if the pin is an input or a GPIO open-drain output
+    fn is_readable(index: u8) -> bool {
+        let bits = Self::ctl_bits(index);
+        bits & 0b11 == 0 || bits & 0b1100 == OutputPortConfiguration::GpioOpenDrain as u8
+    }
+
+    fn modify_ctl(index: u8, field: u8, bits: u8) {
+        assert!(index < 16);
+
+        let offset = (index * 4) % 32;
+        let mask = !((field as u32) << offset);
         let value = (bits as u32) << offset;
         let regs = Self::peripheral();
 
@@ -150,6 +279,44 @@ trait PeripheralAccess {
         regs.bop.write(|w| unsafe { w.bits(1u32 << (16 + index)) });
     }
 
+    /// Sets the pins in the low half and clears the pins in the high half of `bits` in one write
+    #[inline(always)]
+    fn write_bop(bits: u32) {
+        let regs = Self::peripheral();
+
+        // NOTE(unsafe) atomic write to a stateless register
+        regs.bop.write(|w| unsafe { w.bits(bits) });
+    }
+
+    /// Performs the LOCK key write sequence for the pins in `mask`
+    ///
+    /// Returns `true` if the port configuration is now locked
+    fn lock(mask: u16) -> bool {
+        let regs = Self::peripheral();
+
+        // The LOCK register itself stays locked until reset
+        if regs.lock.read().lkk().bit_is_set() {
+            return false;
+        }
+
+        let lkk = 1u32 << 16;
+        let lky = u32::from(mask);
+        interrupt::free(|_| {
+            // NOTE(unsafe) LKy are plain bits, the sequence must not be interrupted
+            regs.lock.write(|w| unsafe { w.bits(lkk | lky) });
+            regs.lock.write(|w| unsafe { w.bits(lky) });
+            regs.lock.write(|w| unsafe { w.bits(lkk | lky) });
+            let _ = regs.lock.read();
+
+            regs.lock.read().lkk().bit_is_set()
+        })
+    }
+
+    #[inline(always)]
+    fn read_istat() -> u16 {
+        Self::peripheral().istat.read().bits() as u16
+    }
+
     #[inline(always)]
     fn is_high(index: u8) -> bool {
         assert!(index < 16);
@@ -182,12 +349,20 @@ macro_rules! gpio {
             use crate::hal::digital::v2::{OutputPin, InputPin, StatefulOutputPin, toggleable};
             use crate::pac::$GPIOX;
             use crate::rcu::{Rcu, Enable, Reset};
+            use crate::afio::Afio;
+            use crate::exti::{Exti, ExtiLine, ExtiPin, LineInUse};
             use super::{
                 PeripheralAccess,
                 PortMode,
+                Speed,
                 InputPortConfiguration,
                 OutputPortConfiguration,
                 Alternate, Floating, GpioExt, Input,
+                Dynamic,
+                PinModeError,
+                Locked,
+                LockablePins,
+                sealed,
                 OpenDrain,
                 Output,
                 PullDown,
@@ -297,7 +472,85 @@ macro_rules! gpio {
 
             pub type $PXx<MODE> = Pxx<MODE>;
 
+            /// Locks the configuration of several pins of this port until the next reset
+            ///
+            /// The LOCK register can only be written once per reset, so all pins needing a lock
+            /// must be passed in a single call, e.g. `lock_pins((pa0, pa1))`. Returns the pins
+            /// unchanged if the port is already locked or the key sequence failed.
+            pub fn lock_pins<P: LockablePins<$GPIOX>>(pins: P) -> Result<P::Locked, P> {
+                if $GPIOX::lock(pins.mask()) {
+                    // NOTE(unsafe) the configuration of the pins is locked
+                    Ok(unsafe { pins.into_locked() })
+                } else {
+                    Err(pins)
+                }
+            }
 
+            /// Group of pins of this port accessed with a single register read or write
+            ///
+            /// Bit `k` of the values passed to [`write`](Self::write), [`set_mask`](Self::set_mask)
+            /// and [`clear_mask`](Self::clear_mask), and returned by [`read`](Self::read),
+            /// corresponds to the `k`th pin passed to [`new`](Self::new). This makes the group
+            /// usable as a data bus for parallel displays and shift registers, even when the bus
+            /// lines aren't wired to consecutive pins.
+            pub struct PortGroup<MODE, const N: usize> {
+                pins: [u8; N],
+                _mode: PhantomData<MODE>,
+            }
+
+            impl<MODE, const N: usize> PortGroup<MODE, N> {
+                /// Groups the pins, least significant bit first
+                pub fn new(pins: [Generic<MODE>; N]) -> Self {
+                    PortGroup {
+                        pins: pins.map(|pin| pin.i),
+                        _mode: PhantomData,
+                    }
+                }
+
+                /// Releases the pins
+                pub fn release(self) -> [Generic<MODE>; N] {
+                    self.pins.map(|i| Generic { i, _mode: PhantomData })
+                }
+
+                /// Returns the mask of port pins in the group
+                pub fn port_mask(&self) -> u16 {
+                    self.group_to_port(!0)
+                }
+
+                /// Reads the input levels of the pins
+                pub fn read(&self) -> u16 {
+                    self.port_to_group($GPIOX::read_istat())
+                }
+
+                fn group_to_port(&self, value: u16) -> u16 {
+                    self.pins.iter().enumerate()
+                        .fold(0, |acc, (k, &i)| acc | (((value >> k) & 1) << i))
+                }
+
+                fn port_to_group(&self, port: u16) -> u16 {
+                    self.pins.iter().enumerate()
+                        .fold(0, |acc, (k, &i)| acc | (((port >> i) & 1) << k))
+                }
+            }
+
+            impl<MODE, const N: usize> PortGroup<Output<MODE>, N> {
+                /// Drives all pins of the group to `value` in one atomic write
+                pub fn write(&mut self, value: u16) {
+                    let set = self.group_to_port(value);
+                    let clear = self.port_mask() & !set;
+                    $GPIOX::write_bop(u32::from(clear) << 16 | u32::from(set));
+                }
+
+                /// Sets the pins selected by `mask` high in one atomic write
+                pub fn set_mask(&mut self, mask: u16) {
+                    $GPIOX::write_bop(u32::from(self.group_to_port(mask)));
+                }
+
+                /// Sets the pins selected by `mask` low in one atomic write
+                pub fn clear_mask(&mut self, mask: u16) {
+                    $GPIOX::write_bop(u32::from(self.group_to_port(mask)) << 16);
+                }
+            }
 
             $(
                 /// Pin
@@ -334,7 +587,7 @@ macro_rules! gpio {
                     /// Configures the pin to operate as an alternate function push-pull output
                     /// pin.
                     pub fn into_alternate_push_pull(self) -> $PXi<Alternate<PushPull>> {
-                        let mode = PortMode::Output50Mhz(OutputPortConfiguration::AfioPushPull);
+                        let mode = PortMode::Output(OutputPortConfiguration::AfioPushPull, Speed::Mhz50);
                         $GPIOX::set_mode($i, mode);
 
                         $PXi { _mode: PhantomData }
@@ -343,7 +596,7 @@ macro_rules! gpio {
                     /// Configures the pin to operate as an alternate function open-drain output
                     /// pin.
                     pub fn into_alternate_open_drain(self) -> $PXi<Alternate<OpenDrain>> {
-                        let mode = PortMode::Output50Mhz(OutputPortConfiguration::AfioOpenDrain);
+                        let mode = PortMode::Output(OutputPortConfiguration::AfioOpenDrain, Speed::Mhz50);
                         $GPIOX::set_mode($i, mode);
 
                         $PXi { _mode: PhantomData }
@@ -394,7 +647,7 @@ macro_rules! gpio {
                             State::Low  => $GPIOX::clear_bit($i),
                         }
 
-                        let mode = PortMode::Output50Mhz(OutputPortConfiguration::GpioOpenDrain);
+                        let mode = PortMode::Output(OutputPortConfiguration::GpioOpenDrain, Speed::Mhz50);
                         $GPIOX::set_mode($i, mode);
 
                         $PXi { _mode: PhantomData }
@@ -419,7 +672,7 @@ macro_rules! gpio {
                             State::Low  => $GPIOX::clear_bit($i),
                         }
 
-                        let mode = PortMode::Output50Mhz(OutputPortConfiguration::GpioPushPull);
+                        let mode = PortMode::Output(OutputPortConfiguration::GpioPushPull, Speed::Mhz50);
                         $GPIOX::set_mode($i, mode);
 
                         $PXi { _mode: PhantomData }
@@ -433,6 +686,154 @@ macro_rules! gpio {
 
                         $PXi { _mode: PhantomData }
                     }
+
+                    /// Configures the pin as a floating input whose mode can be changed at
+                    /// runtime
+                    pub fn into_dynamic(self) -> $PXi<Dynamic> {
+                        self.into_floating_input();
+
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    /// Temporarily configures the pin as a floating input
+                    ///
+                    /// The previous configuration is restored when `f` returns.
+                    pub fn with_floating_input<R>(
+                        &mut self,
+                        f: impl FnOnce(&mut $PXi<Input<Floating>>) -> R,
+                    ) -> R {
+                        self.with_mode(|pin| pin.into_floating_input(), f)
+                    }
+
+                    /// Temporarily configures the pin as a pulled down input
+                    ///
+                    /// The previous configuration is restored when `f` returns.
+                    pub fn with_pull_down_input<R>(
+                        &mut self,
+                        f: impl FnOnce(&mut $PXi<Input<PullDown>>) -> R,
+                    ) -> R {
+                        self.with_mode(|pin| pin.into_pull_down_input(), f)
+                    }
+
+                    /// Temporarily configures the pin as a pulled up input
+                    ///
+                    /// The previous configuration is restored when `f` returns.
+                    pub fn with_pull_up_input<R>(
+                        &mut self,
+                        f: impl FnOnce(&mut $PXi<Input<PullUp>>) -> R,
+                    ) -> R {
+                        self.with_mode(|pin| pin.into_pull_up_input(), f)
+                    }
+
+                    /// Temporarily configures the pin as an open-drain output
+                    ///
+                    /// The previous configuration is restored when `f` returns.
+                    pub fn with_open_drain_output<R>(
+                        &mut self,
+                        initial_state: State,
+                        f: impl FnOnce(&mut $PXi<Output<OpenDrain>>) -> R,
+                    ) -> R {
+                        self.with_mode(|pin| pin.into_open_drain_output_with_state(initial_state), f)
+                    }
+
+                    /// Temporarily configures the pin as a push-pull output
+                    ///
+                    /// The previous configuration is restored when `f` returns.
+                    pub fn with_push_pull_output<R>(
+                        &mut self,
+                        initial_state: State,
+                        f: impl FnOnce(&mut $PXi<Output<PushPull>>) -> R,
+                    ) -> R {
+                        self.with_mode(|pin| pin.into_push_pull_output_with_state(initial_state), f)
+                    }
+
+                    fn with_mode<NEW, R>(
+                        &mut self,
+                        into: impl FnOnce(Self) -> $PXi<NEW>,
+                        f: impl FnOnce(&mut $PXi<NEW>) -> R,
+                    ) -> R {
+                        let saved = $GPIOX::save_config($i);
+                        let mut pin = into($PXi { _mode: PhantomData });
+                        let result = f(&mut pin);
+                        $GPIOX::restore_config($i, saved);
+                        result
+                    }
+                }
+
+                impl $PXi<Dynamic> {
+                    /// Reconfigures the pin as a floating input
+                    pub fn make_floating_input(&mut self) {
+                        let mode = PortMode::Input(InputPortConfiguration::Floating);
+                        $GPIOX::set_mode($i, mode);
+                    }
+
+                    /// Reconfigures the pin as a pulled down input
+                    pub fn make_pull_down_input(&mut self) {
+                        $GPIOX::clear_bit($i); // pull down
+
+                        let mode = PortMode::Input(InputPortConfiguration::Pulled);
+                        $GPIOX::set_mode($i, mode);
+                    }
+
+                    /// Reconfigures the pin as a pulled up input
+                    pub fn make_pull_up_input(&mut self) {
+                        $GPIOX::set_bit($i); // pull up
+
+                        let mode = PortMode::Input(InputPortConfiguration::Pulled);
+                        $GPIOX::set_mode($i, mode);
+                    }
+
+                    /// Reconfigures the pin as an open-drain output, keeping the output level
+                    /// last written
+                    pub fn make_open_drain_output(&mut self) {
+                        let mode = PortMode::Output(OutputPortConfiguration::GpioOpenDrain, Speed::Mhz50);
+                        $GPIOX::set_mode($i, mode);
+                    }
+
+                    /// Reconfigures the pin as a push-pull output, keeping the output level last
+                    /// written
+                    pub fn make_push_pull_output(&mut self) {
+                        let mode = PortMode::Output(OutputPortConfiguration::GpioPushPull, Speed::Mhz50);
+                        $GPIOX::set_mode($i, mode);
+                    }
+
+                    /// Returns `true` if the pin is currently configured as an output
+                    pub fn is_output(&self) -> bool {
+                        $GPIOX::is_output($i)
+                    }
+                }
+
+                impl OutputPin for $PXi<Dynamic> {
+                    type Error = PinModeError;
+                    fn set_high(&mut self) -> Result<(), Self::Error> {
+                        if !$GPIOX::is_output($i) {
+                            return Err(PinModeError::IncorrectMode);
+                        }
+                        $GPIOX::set_bit($i);
+                        Ok(())
+                    }
+
+                    fn set_low(&mut self) -> Result<(), Self::Error> {
+                        if !$GPIOX::is_output($i) {
+                            return Err(PinModeError::IncorrectMode);
+                        }
+                        $GPIOX::clear_bit($i);
+                        Ok(())
+                    }
+                }
+
+                impl InputPin for $PXi<Dynamic> {
+                    type Error = PinModeError;
+                    fn is_high(&self) -> Result<bool, Self::Error> {
+                        if !$GPIOX::is_readable($i) {
+                            return Err(PinModeError::IncorrectMode);
+                        }
+                        Ok($GPIOX::is_high($i))
+                    }
+
+                    fn is_low(&self) -> Result<bool, Self::Error> {
+                        self.is_high().map(|high| !high)
+                    }
                 }
 
                 impl<MODE> $PXi<MODE> where MODE: Active {
@@ -453,6 +854,116 @@ macro_rules! gpio {
                     }
                 }
 
+                impl<MODE> $PXi<Output<MODE>> {
+                    /// Sets the output slew rate. Output pins are configured for 50 MHz by default
+                    pub fn set_speed(&mut self, speed: Speed) {
+                        $GPIOX::set_speed($i, speed);
+                    }
+                }
+
+                impl<MODE> $PXi<Alternate<MODE>> {
+                    /// Sets the output slew rate. Alternate function pins are configured for
+                    /// 50 MHz by default
+                    pub fn set_speed(&mut self, speed: Speed) {
+                        $GPIOX::set_speed($i, speed);
+                    }
+                }
+
+                impl<MODE> ExtiPin for $PXi<Input<MODE>> {
+                    fn exti_line(&self) -> ExtiLine {
+                        // NOTE(unwrap) pin numbers are valid EXTI lines
+                        ExtiLine::from_gpio_line($i).unwrap()
+                    }
+
+                    fn make_interrupt_source(&mut self, afio: &mut Afio) -> Result<(), LineInUse> {
+                        afio.claim_exti_line(Port::$PXx, $i)
+                    }
+
+                    fn release_interrupt_source(&mut self, afio: &mut Afio, exti: &mut Exti) {
+                        if afio.release_exti_line(Port::$PXx, $i) {
+                            exti.disable_interrupt(self.exti_line());
+                        }
+                    }
+                }
+
+                impl<MODE> sealed::Sealed for $PXi<MODE> {}
+
+                impl<MODE> LockablePins<$GPIOX> for $PXi<MODE> where MODE: Active {
+                    type Locked = $PXi<Locked<MODE>>;
+
+                    fn mask(&self) -> u16 {
+                        1 << $i
+                    }
+
+                    unsafe fn into_locked(self) -> Self::Locked {
+                        $PXi { _mode: PhantomData }
+                    }
+                }
+
+                impl<MODE> $PXi<MODE> where MODE: Active {
+                    /// Locks the configuration of the pin until the next reset
+                    ///
+                    /// The LOCK register can only be written once per reset, use the port's
+                    /// `lock_pins` to lock several pins. Returns the pin unchanged if the port is
+                    /// already locked.
+                    pub fn lock(self) -> Result<$PXi<Locked<MODE>>, Self> {
+                        lock_pins(self)
+                    }
+                }
+
+                impl<MODE> OutputPin for $PXi<Locked<Output<MODE>>> {
+                    type Error = Infallible;
+                    fn set_high(&mut self) -> Result<(), Self::Error> {
+                        $GPIOX::set_bit($i);
+                        Ok(())
+                    }
+
+                    fn set_low(&mut self) -> Result<(), Self::Error> {
+                        $GPIOX::clear_bit($i);
+                        Ok(())
+                    }
+                }
+
+                impl<MODE> StatefulOutputPin for $PXi<Locked<Output<MODE>>> {
+                    fn is_set_high(&self) -> Result<bool, Self::Error> {
+                        Ok($GPIOX::is_set_high($i))
+                    }
+
+                    fn is_set_low(&self) -> Result<bool, Self::Error> {
+                        Ok(!$GPIOX::is_set_high($i))
+                    }
+                }
+
+                impl<MODE> toggleable::Default for $PXi<Locked<Output<MODE>>> {}
+
+                impl<MODE> InputPin for $PXi<Locked<Input<MODE>>> {
+                    type Error = Infallible;
+                    fn is_high(&self) -> Result<bool, Self::Error> {
+                        Ok($GPIOX::is_high($i))
+                    }
+
+                    fn is_low(&self) -> Result<bool, Self::Error> {
+                        Ok(!$GPIOX::is_high($i))
+                    }
+                }
+
+                impl InputPin for $PXi<Locked<Output<OpenDrain>>> {
+                    type Error = Infallible;
+                    fn is_high(&self) -> Result<bool, Self::Error> {
+                        Ok($GPIOX::is_high($i))
+                    }
+
+                    fn is_low(&self) -> Result<bool, Self::Error> {
+                        Ok(!$GPIOX::is_high($i))
+                    }
+                }
+
+                impl<MODE> From<$PXi<MODE>> for Generic<MODE> where MODE: Active {
+                    fn from(pin: $PXi<MODE>) -> Self {
+                        pin.into_generic()
+                    }
+                }
+
                 impl<MODE> OutputPin for $PXi<Output<MODE>> {
                     type Error = Infallible;
                     fn set_high(&mut self) -> Result<(), Self::Error> {
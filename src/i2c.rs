@@ -7,6 +7,11 @@ use crate::pac::{I2C0, I2C1};
 use crate::rcu::{Rcu, Clocks, Enable, Reset, BaseFrequency};
 use crate::time::Hertz;
 use crate::afio::{Afio, Remap};
+use crate::dma::{
+    dma0, ChannelConfig, Priority, Receive, RxDma, Transfer, Transmit, TransferPayload, TxDma, R, W,
+};
+use core::sync::atomic::{self, Ordering};
+use embedded_dma::{StaticReadBuffer, StaticWriteBuffer};
 use riscv::register::mcycle;
 use nb::Error::{Other, WouldBlock};
 use nb::{Error as NbError, Result as NbResult};
@@ -213,13 +218,13 @@ fn blocking_i2c<I2C, PINS>(
     addr_timeout_us: u32,
     data_timeout_us: u32,
 ) -> BlockingI2c<I2C, PINS> {
-    let sysclk_mhz = clocks.sysclk().0 / 1_000_000;
+    let hclk_mhz = clocks.hclk().0 / 1_000_000;
     BlockingI2c {
         nb: i2c,
-        start_timeout: start_timeout_us * sysclk_mhz,
+        start_timeout: start_timeout_us * hclk_mhz,
         start_retries,
-        addr_timeout: addr_timeout_us * sysclk_mhz,
-        data_timeout: data_timeout_us * sysclk_mhz,
+        addr_timeout: addr_timeout_us * hclk_mhz,
+        data_timeout: data_timeout_us * hclk_mhz,
     }
 }
 
@@ -568,3 +573,285 @@ hal! {
     I2C0: (_i2c0),
     I2C1: (_i2c1),
 }
+
+macro_rules! take_error {
+    ($i2c:expr) => {{
+        let stat0 = $i2c.stat0.read();
+
+        if stat0.berr().bit_is_set() {
+            $i2c.stat0.modify(|_, w| w.berr().clear_bit());
+            Some(Error::Bus)
+        } else if stat0.lostarb().bit_is_set() {
+            $i2c.stat0.modify(|_, w| w.lostarb().clear_bit());
+            Some(Error::Arbitration)
+        } else if stat0.aerr().bit_is_set() {
+            $i2c.stat0.modify(|_, w| w.aerr().clear_bit());
+            Some(Error::Acknowledge)
+        } else if stat0.ouerr().bit_is_set() {
+            $i2c.stat0.modify(|_, w| w.ouerr().clear_bit());
+            Some(Error::Overrun)
+        } else {
+            None
+        }
+    }};
+}
+
+/// I2C master transmitter with a DMA channel attached
+pub type I2cTxDma<I2C, PINS, CHANNEL> = TxDma<BlockingI2c<I2C, PINS>, CHANNEL>;
+
+/// I2C master receiver with a DMA channel attached
+pub type I2cRxDma<I2C, PINS, CHANNEL> = RxDma<BlockingI2c<I2C, PINS>, CHANNEL>;
+
+/// DMA transfer on an I2C bus
+///
+/// The START condition and the address are sent before the transfer is returned. The data phase
+/// runs on the DMA channel, and `wait` generates the STOP condition and reports errors.
+pub struct I2cTransfer<MODE, BUFFER, PAYLOAD>
+where
+    PAYLOAD: TransferPayload,
+{
+    transfer: Transfer<MODE, BUFFER, PAYLOAD>,
+    /// Error raised before the data phase was started
+    error: Option<NbError<Error>>,
+    stop_sent: bool,
+    /// Cycles `wait` spins before giving up
+    timeout: u32,
+}
+
+macro_rules! i2c_dma {
+    ($I2CX:ident, $TXCH:ty, $RXCH:ty) => {
+        impl<PINS> BlockingI2c<$I2CX, PINS> {
+            /// Attaches the transmit DMA channel of this I2C
+            pub fn with_tx_dma(self, channel: $TXCH) -> I2cTxDma<$I2CX, PINS, $TXCH> {
+                I2cTxDma { payload: self, channel }
+            }
+
+            /// Attaches the receive DMA channel of this I2C
+            pub fn with_rx_dma(self, channel: $RXCH) -> I2cRxDma<$I2CX, PINS, $RXCH> {
+                I2cRxDma { payload: self, channel }
+            }
+
+            /// Collects the outcome of a stopped DMA transfer and ends it with a STOP condition
+            fn end_dma_transfer(&mut self, error: Option<NbError<Error>>, stop_sent: bool) -> NbResult<(), Error> {
+                let result = match error {
+                    Some(error) => Err(error),
+                    None => match take_error!(self.nb.i2c) {
+                        Some(error) => Err(Other(error)),
+                        None => Ok(()),
+                    },
+                };
+                if !stop_sent && result != Err(Other(Error::Arbitration)) {
+                    self.nb.send_stop();
+                }
+                let result = result.and_then(|_| busy_wait_cycles!(self.nb.wait_for_stop(), self.data_timeout));
+                // Leave ACKEN set, as the blocking read does
+                self.nb.i2c.ctl0.modify(|_, w| w.acken().set_bit());
+                result
+            }
+        }
+
+        impl<PINS> Transmit for I2cTxDma<$I2CX, PINS, $TXCH> {
+            type TxChannel = $TXCH;
+            type ReceivedWord = u8;
+        }
+
+        impl<PINS> Receive for I2cRxDma<$I2CX, PINS, $RXCH> {
+            type RxChannel = $RXCH;
+            type TransmittedWord = u8;
+        }
+
+        impl<PINS> TransferPayload for I2cTxDma<$I2CX, PINS, $TXCH> {
+            fn start(&mut self) {
+                self.channel.start();
+                self.payload.nb.i2c.ctl1.modify(|_, w| w.dmaon().set_bit());
+            }
+            fn stop(&mut self) {
+                self.payload.nb.i2c.ctl1.modify(|_, w| w.dmaon().clear_bit());
+                self.channel.stop();
+            }
+        }
+
+        impl<PINS> TransferPayload for I2cRxDma<$I2CX, PINS, $RXCH> {
+            fn start(&mut self) {
+                self.channel.start();
+                self.payload.nb.i2c.ctl1.modify(|_, w| w.dmaon().set_bit());
+            }
+            fn stop(&mut self) {
+                self.payload.nb.i2c.ctl1.modify(|_, w| w.dmaon().clear_bit().dmalst().clear_bit());
+                self.channel.stop();
+            }
+        }
+
+        impl<PINS> I2cTxDma<$I2CX, PINS, $TXCH> {
+            /// Returns the blocking I2C and the DMA channel
+            pub fn release(mut self) -> (BlockingI2c<$I2CX, PINS>, $TXCH) {
+                self.stop();
+                let I2cTxDma { payload, channel } = self;
+                (payload, channel)
+            }
+
+            /// Writes `buffer` to the device at `addr`
+            ///
+            /// Panics if `buffer` is empty.
+            pub fn write<B>(mut self, addr: u8, buffer: B) -> I2cTransfer<R, B, Self>
+            where
+                B: StaticReadBuffer<Word = u8>,
+            {
+                // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                // until the end of the transfer.
+                let (ptr, len) = unsafe { buffer.static_read_buffer() };
+                assert!(len > 0, "empty I2C DMA transfer");
+
+                let addressed = self.payload.send_start_and_wait()
+                    .and_then(|_| self.payload.send_addr_and_wait(addr, false));
+                let timeout = self.payload.data_timeout.saturating_mul(len as u32 + 1);
+                if let Err(error) = addressed {
+                    let transfer = Transfer::r(buffer, self);
+                    return I2cTransfer { transfer, error: Some(error), stop_sent: true, timeout };
+                }
+
+                unsafe {
+                    self.channel.set_peripheral_address(&(*$I2CX::ptr()).data as *const _ as u32, false);
+                    self.channel.set_memory_address(ptr as u32, true);
+                }
+                self.channel.set_transfer_length(len);
+
+                atomic::compiler_fence(Ordering::Release);
+                self.channel.configure(ChannelConfig::default()
+                    .priority(Priority::Medium)
+                    .word::<u8>()
+                    .memory_increment(true)
+                );
+                self.channel.ctl().modify(|_, w| w.m2m().clear_bit().dir().set_bit());
+                self.start();
+
+                // Reading STAT0 then STAT1 clears ADDSEND and starts the data phase
+                self.payload.nb.i2c.stat0.read();
+                self.payload.nb.i2c.stat1.read();
+
+                I2cTransfer { transfer: Transfer::r(buffer, self), error: None, stop_sent: false, timeout }
+            }
+        }
+
+        impl<PINS> I2cRxDma<$I2CX, PINS, $RXCH> {
+            /// Returns the blocking I2C and the DMA channel
+            pub fn release(mut self) -> (BlockingI2c<$I2CX, PINS>, $RXCH) {
+                self.stop();
+                let I2cRxDma { payload, channel } = self;
+                (payload, channel)
+            }
+
+            /// Fills `buffer` with data read from the device at `addr`
+            ///
+            /// Panics if `buffer` is empty.
+            pub fn read<B>(mut self, addr: u8, mut buffer: B) -> I2cTransfer<W, B, Self>
+            where
+                B: StaticWriteBuffer<Word = u8>,
+            {
+                // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                // until the end of the transfer.
+                let (ptr, len) = unsafe { buffer.static_write_buffer() };
+                assert!(len > 0, "empty I2C DMA transfer");
+
+                let addressed = self.payload.send_start_and_wait()
+                    .and_then(|_| self.payload.send_addr_and_wait(addr, true));
+                let timeout = self.payload.data_timeout.saturating_mul(len as u32 + 1);
+                if let Err(error) = addressed {
+                    let transfer = Transfer::w(buffer, self);
+                    return I2cTransfer { transfer, error: Some(error), stop_sent: true, timeout };
+                }
+
+                unsafe {
+                    self.channel.set_peripheral_address(&(*$I2CX::ptr()).data as *const _ as u32, false);
+                    self.channel.set_memory_address(ptr as u32, true);
+                }
+                self.channel.set_transfer_length(len);
+
+                atomic::compiler_fence(Ordering::Release);
+                self.channel.configure(ChannelConfig::default()
+                    .priority(Priority::Medium)
+                    .word::<u8>()
+                    .memory_increment(true)
+                );
+                self.channel.ctl().modify(|_, w| w.m2m().clear_bit().dir().clear_bit());
+
+                // With DMALST the byte completing the DMA transfer is NACKed. A single byte
+                // has to be NACKed before ADDSEND is cleared.
+                let i2c = &self.payload.nb.i2c;
+                i2c.ctl0.modify(|_, w| w.acken().bit(len > 1));
+                i2c.ctl1.modify(|_, w| w.dmalst().set_bit());
+                self.start();
+
+                // Reading STAT0 then STAT1 clears ADDSEND and starts the data phase
+                self.payload.nb.i2c.stat0.read();
+                self.payload.nb.i2c.stat1.read();
+
+                let stop_sent = len == 1;
+                if stop_sent {
+                    self.payload.nb.send_stop();
+                }
+
+                I2cTransfer { transfer: Transfer::w(buffer, self), error: None, stop_sent, timeout }
+            }
+        }
+
+        impl<B, PINS> I2cTransfer<R, B, I2cTxDma<$I2CX, PINS, $TXCH>> {
+            /// Returns `true` once the last byte has been sent or an error occurred
+            pub fn is_done(&self) -> bool {
+                let payload = self.transfer.payload();
+                let stat0 = payload.payload.nb.i2c.stat0.read();
+                self.error.is_some()
+                    || stat0.berr().bit_is_set()
+                    || stat0.lostarb().bit_is_set()
+                    || stat0.aerr().bit_is_set()
+                    || (!payload.channel.in_progress() && stat0.btc().bit_is_set())
+            }
+
+            /// Waits for the transfer to end, generates the STOP condition and returns the
+            /// outcome along with the buffer and the DMA handle
+            ///
+            /// Returns `WouldBlock` if the transfer didn't end within the data timeout per byte.
+            pub fn wait(self) -> (Result<(), NbError<Error>>, B, I2cTxDma<$I2CX, PINS, $TXCH>) {
+                let waited = busy_wait_cycles!(if self.is_done() { Ok(()) } else { Err(WouldBlock) }, self.timeout);
+                let I2cTransfer { transfer, error, stop_sent, .. } = self;
+                atomic::compiler_fence(Ordering::Acquire);
+
+                let (buffer, mut payload) = transfer.into_parts();
+                payload.stop();
+                let result = payload.payload.end_dma_transfer(error.or(waited.err()), stop_sent);
+                (result, buffer, payload)
+            }
+        }
+
+        impl<B, PINS> I2cTransfer<W, B, I2cRxDma<$I2CX, PINS, $RXCH>> {
+            /// Returns `true` once the last byte has been received or an error occurred
+            pub fn is_done(&self) -> bool {
+                let payload = self.transfer.payload();
+                let stat0 = payload.payload.nb.i2c.stat0.read();
+                self.error.is_some()
+                    || stat0.berr().bit_is_set()
+                    || stat0.lostarb().bit_is_set()
+                    || stat0.ouerr().bit_is_set()
+                    || !payload.channel.in_progress()
+            }
+
+            /// Waits for the transfer to end, generates the STOP condition and returns the
+            /// outcome along with the buffer and the DMA handle
+            ///
+            /// Returns `WouldBlock` if the transfer didn't end within the data timeout per byte.
+            pub fn wait(self) -> (Result<(), NbError<Error>>, B, I2cRxDma<$I2CX, PINS, $RXCH>) {
+                let waited = busy_wait_cycles!(if self.is_done() { Ok(()) } else { Err(WouldBlock) }, self.timeout);
+                let I2cTransfer { transfer, error, stop_sent, .. } = self;
+                atomic::compiler_fence(Ordering::Acquire);
+
+                let (buffer, mut payload) = transfer.into_parts();
+                payload.stop();
+                let result = payload.payload.end_dma_transfer(error.or(waited.err()), stop_sent);
+                (result, buffer, payload)
+            }
+        }
+    };
+}
+
+i2c_dma!(I2C0, dma0::C5, dma0::C6);
+i2c_dma!(I2C1, dma0::C3, dma0::C4);
@@ -12,6 +12,7 @@ use embedded_hal as hal;
 pub mod adc;
 pub mod afio;
 pub mod backup_domain;
+pub mod calibration;
 pub mod delay;
 pub mod dma;
 pub mod eclic;
@@ -19,6 +20,10 @@ pub mod exmc;
 pub mod exti;
 pub mod gpio;
 pub mod i2c;
+pub mod monotonic;
+pub mod mtimer;
+#[cfg(target_arch = "riscv32")]
+pub mod perf;
 pub mod prelude;
 pub mod pwm;
 pub mod rcu;
@@ -28,4 +33,6 @@ pub mod signature;
 pub mod spi;
 pub mod time;
 pub mod timer;
+#[cfg(target_arch = "riscv32")]
+pub mod trap;
 pub mod watchdog;
@@ -0,0 +1,316 @@
+//! Monotonic time base on the core machine timer
+//!
+//! [`Mono`] turns an [`MTimer`] into a non-wrapping 64-bit time base with a single alarm driven
+//! by `mtimecmp`. At SysTick frequencies `mtime` takes thousands of years to overflow, so
+//! [`Instant`]s never wrap.
+//!
+//! With the `rtic-monotonic` feature `Mono` implements `rtic_monotonic::Monotonic`. With the
+//! `embassy-time-driver` feature the [`embassy`] module provides the embassy time driver.
+
+use crate::mtimer::MTimer;
+use core::cmp::Ordering;
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+
+/// Point in time counted in `mtime` ticks at `HZ`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Instant<const HZ: u32> {
+    ticks: u64,
+}
+
+impl<const HZ: u32> Instant<HZ> {
+    /// Creates an instant `ticks` periods after the timer was reset
+    pub const fn from_ticks(ticks: u64) -> Self {
+        Self { ticks }
+    }
+
+    /// Returns the number of ticks since the timer was reset
+    pub const fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Returns the time elapsed since `earlier`, or `None` if `earlier` is later than `self`
+    pub const fn checked_duration_since(&self, earlier: Self) -> Option<Duration<HZ>> {
+        match self.ticks.checked_sub(earlier.ticks) {
+            Some(ticks) => Some(Duration::from_ticks(ticks)),
+            None => None,
+        }
+    }
+}
+
+impl<const HZ: u32> PartialOrd for Instant<HZ> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<const HZ: u32> Ord for Instant<HZ> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.ticks.cmp(&other.ticks)
+    }
+}
+
+impl<const HZ: u32> Add<Duration<HZ>> for Instant<HZ> {
+    type Output = Self;
+
+    fn add(self, rhs: Duration<HZ>) -> Self {
+        Self::from_ticks(self.ticks + rhs.ticks)
+    }
+}
+
+impl<const HZ: u32> AddAssign<Duration<HZ>> for Instant<HZ> {
+    fn add_assign(&mut self, rhs: Duration<HZ>) {
+        *self = *self + rhs;
+    }
+}
+
+impl<const HZ: u32> Sub<Duration<HZ>> for Instant<HZ> {
+    type Output = Self;
+
+    fn sub(self, rhs: Duration<HZ>) -> Self {
+        Self::from_ticks(self.ticks - rhs.ticks)
+    }
+}
+
+impl<const HZ: u32> SubAssign<Duration<HZ>> for Instant<HZ> {
+    fn sub_assign(&mut self, rhs: Duration<HZ>) {
+        *self = *self - rhs;
+    }
+}
+
+impl<const HZ: u32> Sub<Instant<HZ>> for Instant<HZ> {
+    type Output = Duration<HZ>;
+
+    fn sub(self, rhs: Instant<HZ>) -> Duration<HZ> {
+        Duration::from_ticks(self.ticks - rhs.ticks)
+    }
+}
+
+/// Span of time counted in `mtime` ticks at `HZ`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Duration<const HZ: u32> {
+    ticks: u64,
+}
+
+impl<const HZ: u32> Duration<HZ> {
+    /// Creates a duration of `ticks` timer periods
+    pub const fn from_ticks(ticks: u64) -> Self {
+        Self { ticks }
+    }
+
+    /// Creates a duration of `us` microseconds, rounded up to whole ticks
+    pub const fn from_micros(us: u64) -> Self {
+        Self::from_ticks((us * HZ as u64).div_ceil(1_000_000))
+    }
+
+    /// Creates a duration of `ms` milliseconds, rounded up to whole ticks
+    pub const fn from_millis(ms: u64) -> Self {
+        Self::from_ticks((ms * HZ as u64).div_ceil(1_000))
+    }
+
+    /// Creates a duration of `s` seconds
+    pub const fn from_secs(s: u64) -> Self {
+        Self::from_ticks(s * HZ as u64)
+    }
+
+    /// Returns the number of ticks
+    pub const fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Returns the duration in whole microseconds
+    pub const fn to_micros(&self) -> u64 {
+        self.ticks * 1_000_000 / HZ as u64
+    }
+
+    /// Returns the duration in whole milliseconds
+    pub const fn to_millis(&self) -> u64 {
+        self.ticks * 1_000 / HZ as u64
+    }
+}
+
+impl<const HZ: u32> Add for Duration<HZ> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::from_ticks(self.ticks + rhs.ticks)
+    }
+}
+
+impl<const HZ: u32> Sub for Duration<HZ> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::from_ticks(self.ticks - rhs.ticks)
+    }
+}
+
+/// Monotonic timer on `mtime` counting at `HZ`
+///
+/// `HZ` must equal the SysTick frequency the `MTimer` was created with, e.g. 27 MHz for a
+/// 108 MHz core clock.
+pub struct Mono<const HZ: u32> {
+    timer: MTimer,
+}
+
+impl<const HZ: u32> Mono<HZ> {
+    /// Creates the monotonic timer, disarming the alarm
+    ///
+    /// Panics if the timer doesn't count at `HZ`.
+    pub fn new(mut timer: MTimer) -> Self {
+        assert_eq!(timer.frequency().0, HZ, "mtime frequency doesn't match HZ");
+
+        timer.cancel();
+        Self { timer }
+    }
+
+    /// Releases the core timer
+    pub fn free(self) -> MTimer {
+        self.timer
+    }
+
+    /// Returns the current time
+    pub fn now(&self) -> Instant<HZ> {
+        Instant::from_ticks(self.timer.now())
+    }
+
+    /// Raises `INT_TMR` at `instant`, immediately if it has already passed
+    pub fn set_alarm(&mut self, instant: Instant<HZ>) {
+        self.timer.set_compare(instant.ticks());
+    }
+
+    /// Disarms the alarm and clears the pending `INT_TMR`
+    pub fn clear_alarm(&mut self) {
+        self.timer.cancel();
+    }
+}
+
+#[cfg(feature = "rtic-monotonic")]
+impl<const HZ: u32> rtic_monotonic::Monotonic for Mono<HZ> {
+    type Instant = Instant<HZ>;
+    type Duration = Duration<HZ>;
+
+    fn now(&mut self) -> Self::Instant {
+        Mono::now(self)
+    }
+
+    fn set_compare(&mut self, instant: Self::Instant) {
+        self.set_alarm(instant);
+    }
+
+    fn clear_compare_flag(&mut self) {
+        // INT_TMR is level triggered and only cleared by moving mtimecmp
+        self.clear_alarm();
+    }
+
+    fn zero() -> Self::Instant {
+        Instant::from_ticks(0)
+    }
+
+    unsafe fn reset(&mut self) {
+        self.timer.reset_counter();
+    }
+}
+
+/// Embassy time driver on the core machine timer
+///
+/// The driver provides a single alarm. Call `init()` once with the `MTimer` and
+/// `on_interrupt()` from the `INT_TMR` handler.
+#[cfg(feature = "embassy-time-driver")]
+pub mod embassy {
+    use crate::mtimer::MTimer;
+    use core::cell::{Cell, RefCell};
+    use embassy_time_driver::{AlarmHandle, Driver, TICK_HZ};
+    use riscv::interrupt::{self, Mutex};
+
+    struct Callback {
+        func: fn(*mut ()),
+        ctx: *mut (),
+    }
+
+    // NOTE(unsafe) the context pointer is only handed back to the callback
+    unsafe impl Send for Callback {}
+
+    struct MTimerDriver {
+        timer: Mutex<RefCell<Option<MTimer>>>,
+        allocated: Mutex<Cell<bool>>,
+        callback: Mutex<RefCell<Option<Callback>>>,
+    }
+
+    embassy_time_driver::time_driver_impl!(static DRIVER: MTimerDriver = MTimerDriver {
+        timer: Mutex::new(RefCell::new(None)),
+        allocated: Mutex::new(Cell::new(false)),
+        callback: Mutex::new(RefCell::new(None)),
+    });
+
+    /// Hands the core timer to the embassy time driver
+    pub fn init(mut timer: MTimer) {
+        timer.cancel();
+        interrupt::free(|cs| DRIVER.timer.borrow(cs).replace(Some(timer)));
+    }
+
+    /// Handles `INT_TMR`, calling the alarm callback
+    pub fn on_interrupt() {
+        let callback = interrupt::free(|cs| {
+            if let Some(timer) = DRIVER.timer.borrow(cs).borrow_mut().as_mut() {
+                timer.cancel();
+            }
+            DRIVER.callback.borrow(cs).borrow().as_ref().map(|cb| (cb.func, cb.ctx))
+        });
+
+        if let Some((func, ctx)) = callback {
+            func(ctx);
+        }
+    }
+
+    impl MTimerDriver {
+        /// Runs `f` on the timer, panicking if `init` wasn't called
+        fn with_timer<R>(&self, f: impl FnOnce(&mut MTimer) -> R) -> R {
+            interrupt::free(|cs| {
+                let mut timer = self.timer.borrow(cs).borrow_mut();
+                f(timer.as_mut().expect("embassy time driver not initialized"))
+            })
+        }
+    }
+
+    impl Driver for MTimerDriver {
+        fn now(&self) -> u64 {
+            self.with_timer(|timer| {
+                let hz = u128::from(timer.frequency().0);
+                (u128::from(timer.now()) * u128::from(TICK_HZ) / hz) as u64
+            })
+        }
+
+        unsafe fn allocate_alarm(&self) -> Option<AlarmHandle> {
+            interrupt::free(|cs| {
+                let allocated = self.allocated.borrow(cs);
+                if allocated.replace(true) {
+                    None
+                } else {
+                    // NOTE(unsafe) the only alarm
+                    Some(unsafe { AlarmHandle::new(0) })
+                }
+            })
+        }
+
+        fn set_alarm_callback(&self, _alarm: AlarmHandle, callback: fn(*mut ()), ctx: *mut ()) {
+            interrupt::free(|cs| {
+                self.callback.borrow(cs).replace(Some(Callback { func: callback, ctx }));
+            });
+        }
+
+        fn set_alarm(&self, _alarm: AlarmHandle, timestamp: u64) -> bool {
+            self.with_timer(|timer| {
+                // Round up so the alarm never fires early
+                let hz = u128::from(timer.frequency().0);
+                let ticks = (u128::from(timestamp) * hz).div_ceil(u128::from(TICK_HZ));
+                let ticks = u64::try_from(ticks).unwrap_or(u64::MAX);
+
+                if ticks <= timer.now() {
+                    return false;
+                }
+                timer.set_compare(ticks);
+                true
+            })
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Core machine timer (mtime)
+//!
+//! The Bumblebee core timer is a 64-bit counter running at the SysTick frequency
+//! ([`Clocks::systick()`](crate::rcu::Clocks::systick)). When `mtime` reaches `mtimecmp` the
+//! `INT_TMR` interrupt is raised; it stays pending until `mtimecmp` is moved past `mtime`.
+//!
+//! The same unit holds the `msip` bit driving the core software interrupt `INT_SFT`, see
+//! [`SoftwareInterrupt`].
+
+use crate::eclic::{EclicExt, Level, Priority, TriggerType, VectorMode};
+use crate::pac::{Interrupt, CTIMER, ECLIC};
+use crate::rcu::Clocks;
+use crate::time::Hertz;
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+
+/// Wraps the core timer (CTIMER) peripheral
+pub struct MTimer {
+    ctimer: CTIMER,
+    frequency: u32,
+    /// Ticks between periodic interrupts, 0 for one-shot
+    period: u64,
+}
+
+impl MTimer {
+    /// Wraps the core timer, leaving `INT_TMR` disarmed
+    pub fn new(ctimer: CTIMER, clocks: &Clocks) -> Self {
+        let mut timer = MTimer {
+            ctimer,
+            frequency: clocks.systick().0,
+            period: 0,
+        };
+        timer.cancel();
+        timer
+    }
+
+    /// Releases the CTIMER peripheral
+    pub fn free(self) -> CTIMER {
+        self.ctimer
+    }
+
+    /// Returns the counting frequency of `mtime`
+    pub fn frequency(&self) -> Hertz {
+        Hertz(self.frequency)
+    }
+
+    /// Reads the 64-bit `mtime` counter
+    pub fn now(&self) -> u64 {
+        loop {
+            let hi = self.ctimer.mtime_hi.read().bits();
+            let lo = self.ctimer.mtime_lo.read().bits();
+            if hi == self.ctimer.mtime_hi.read().bits() {
+                return u64::from(hi) << 32 | u64::from(lo);
+            }
+        }
+    }
+
+    /// Reads the 64-bit `mtimecmp` register
+    pub fn compare(&self) -> u64 {
+        let hi = self.ctimer.mtimecmp_hi.read().bits();
+        let lo = self.ctimer.mtimecmp_lo.read().bits();
+        u64::from(hi) << 32 | u64::from(lo)
+    }
+
+    /// Sets `mtimecmp`, the `mtime` value at which `INT_TMR` is raised
+    pub fn set_compare(&mut self, value: u64) {
+        // Raise the low half first so no intermediate value triggers the interrupt
+        // NOTE(unsafe) plain 32-bit registers
+        self.ctimer.mtimecmp_lo.write(|w| unsafe { w.bits(u32::MAX) });
+        self.ctimer.mtimecmp_hi.write(|w| unsafe { w.bits((value >> 32) as u32) });
+        self.ctimer.mtimecmp_lo.write(|w| unsafe { w.bits(value as u32) });
+    }
+
+    /// Raises `INT_TMR` once, `ticks` periods of `mtime` from now
+    ///
+    /// Deadlines past the end of the 64-bit counter are clamped to `u64::MAX`.
+    pub fn start_oneshot(&mut self, ticks: u64) {
+        self.period = 0;
+        let now = self.now();
+        self.set_compare(now.saturating_add(ticks));
+    }
+
+    /// Raises `INT_TMR` periodically at `freq`
+    ///
+    /// [`handle_interrupt`](Self::handle_interrupt) must be called from the `INT_TMR` handler to
+    /// schedule the next tick.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `freq` is zero or above the `mtime` frequency.
+    pub fn start_periodic(&mut self, freq: impl Into<Hertz>) {
+        let freq = freq.into().0;
+        assert!(0 < freq && freq <= self.frequency);
+        self.period = u64::from(self.frequency / freq);
+        let now = self.now();
+        self.set_compare(now.saturating_add(self.period));
+    }
+
+    /// Disarms `INT_TMR`
+    pub fn cancel(&mut self) {
+        self.period = 0;
+        self.set_compare(u64::MAX);
+    }
+
+    /// Acknowledges `INT_TMR`
+    ///
+    /// Schedules the next tick in periodic mode and disarms the interrupt otherwise. Periodic
+    /// ticks are scheduled relative to the previous deadline, so handler latency doesn't
+    /// accumulate.
+    pub fn handle_interrupt(&mut self) {
+        if self.period == 0 {
+            self.set_compare(u64::MAX);
+        } else {
+            let next = self.compare().saturating_add(self.period);
+            self.set_compare(next);
+        }
+    }
+
+    /// Restarts `mtime` from zero
+    pub fn reset_counter(&mut self) {
+        // NOTE(unsafe) plain 32-bit registers
+        self.ctimer.mtime_lo.write(|w| unsafe { w.bits(0) });
+        self.ctimer.mtime_hi.write(|w| unsafe { w.bits(0) });
+        self.ctimer.mtime_lo.write(|w| unsafe { w.bits(0) });
+    }
+
+    /// Stops or resumes counting of `mtime`
+    pub fn set_stopped(&mut self, stopped: bool) {
+        self.ctimer.mstop.write(|w| w.timestop().bit(stopped));
+    }
+}
+
+/// Core software interrupt (`INT_SFT`) driven by the `msip` bit
+///
+/// `INT_SFT` is level triggered: it stays pending until the handler calls
+/// [`unpend`](Self::unpend). These functions only access `msip`, so they can be used from any
+/// context while an [`MTimer`] owns the rest of the timer unit.
+///
+/// ```no_run
+/// use gd32vf103xx_hal::eclic::{Level, Priority};
+/// use gd32vf103xx_hal::mtimer::SoftwareInterrupt;
+///
+/// // NOTE(unsafe) no critical section relies on `INT_SFT` being masked
+/// unsafe { SoftwareInterrupt::setup(Level::L1, Priority::P0) };
+/// // From a higher priority handler
+/// SoftwareInterrupt::pend();
+///
+/// #[allow(non_snake_case)]
+/// #[unsafe(no_mangle)]
+/// fn INT_SFT() {
+///     SoftwareInterrupt::unpend();
+///     // Deferred work
+/// }
+/// ```
+pub struct SoftwareInterrupt;
+
+impl SoftwareInterrupt {
+    /// Configures `INT_SFT` in the ECLIC with `level` and `priority` and enables it
+    ///
+    /// # Safety
+    ///
+    /// Unmasking `INT_SFT` can break mask-based critical sections, as with
+    /// [`EclicExt::unmask`].
+    pub unsafe fn setup(level: Level, priority: Priority) {
+        let mode = VectorMode::NonVectored;
+        ECLIC::setup(Interrupt::INT_SFT, TriggerType::Level, level, priority, mode);
+        unsafe { ECLIC::unmask(Interrupt::INT_SFT) };
+    }
+
+    /// Raises `INT_SFT`
+    #[inline]
+    pub fn pend() {
+        // NOTE(unsafe) atomic write to a register only holding msip
+        unsafe { (*CTIMER::ptr()).msip.write(|w| w.msip().set_bit()) };
+    }
+
+    /// Clears `INT_SFT`
+    #[inline]
+    pub fn unpend() {
+        // NOTE(unsafe) atomic write to a register only holding msip
+        unsafe { (*CTIMER::ptr()).msip.write(|w| w.msip().clear_bit()) };
+    }
+
+    /// Returns `true` if `INT_SFT` is raised
+    #[inline]
+    pub fn is_pending() -> bool {
+        unsafe { (*CTIMER::ptr()).msip.read().msip().bit_is_set() }
+    }
+}
+
+impl DelayUs<u64> for MTimer {
+    fn delay_us(&mut self, us: u64) {
+        let t0 = self.now();
+        let ticks = us.saturating_mul(self.frequency as u64) / 1_000_000;
+        while self.now().wrapping_sub(t0) <= ticks { }
+    }
+}
+
+impl DelayUs<u32> for MTimer {
+    #[inline(always)]
+    fn delay_us(&mut self, us: u32) {
+        self.delay_us(us as u64)
+    }
+}
+
+// Implemented for constructions like `delay.delay_us(50_000);`
+impl DelayUs<i32> for MTimer {
+    #[inline(always)]
+    fn delay_us(&mut self, us: i32) {
+        assert!(us >= 0);
+        self.delay_us(us as u32);
+    }
+}
+
+impl DelayUs<u16> for MTimer {
+    #[inline(always)]
+    fn delay_us(&mut self, us: u16) {
+        self.delay_us(us as u32)
+    }
+}
+
+impl DelayUs<u8> for MTimer {
+    #[inline(always)]
+    fn delay_us(&mut self, us: u8) {
+        self.delay_us(us as u32)
+    }
+}
+
+impl DelayMs<u32> for MTimer {
+    fn delay_ms(&mut self, ms: u32) {
+        self.delay_us((ms as u64) * 1000)
+    }
+}
+
+// Implemented for constructions like `delay.delay_ms(50_000);`
+impl DelayMs<i32> for MTimer {
+    #[inline(always)]
+    fn delay_ms(&mut self, ms: i32) {
+        assert!(ms >= 0);
+        self.delay_ms(ms as u32);
+    }
+}
+
+impl DelayMs<u16> for MTimer {
+    #[inline(always)]
+    fn delay_ms(&mut self, ms: u16) {
+        self.delay_ms(ms as u32)
+    }
+}
+
+impl DelayMs<u8> for MTimer {
+    #[inline(always)]
+    fn delay_ms(&mut self, ms: u8) {
+        self.delay_ms(ms as u32)
+    }
+}
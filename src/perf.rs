@@ -0,0 +1,255 @@
+//! Performance counters
+//!
+//! The Bumblebee core counts clock cycles in `mcycle` and retired instructions in `minstret`.
+//! Both are 64-bit and run from reset; either can be paused through `mcountinhibit`. When a counter
+//! overflows, the core raises `INT_PMOVI`.
+//!
+//! [`Stopwatch`] measures code in place:
+//!
+//! ```no_run
+//! # fn filter() {}
+//! use gd32vf103xx_hal as hal;
+//! use hal::pac::Peripherals;
+//! use hal::perf::Stopwatch;
+//! use hal::rcu::RcuExt;
+//!
+//! let dp = Peripherals::take().unwrap();
+//! let rcu = dp.RCU.configure().freeze();
+//!
+//! let mut sw = Stopwatch::new(&rcu.clocks);
+//! let ((), counts) = sw.measure(filter);
+//! let us = sw.to_micros(counts.cycles);
+//! ```
+
+use crate::eclic::{EclicExt, Level, Priority, TriggerType, VectorMode};
+use crate::pac::{Interrupt, ECLIC};
+use crate::rcu::Clocks;
+use crate::time::Hertz;
+use core::arch::asm;
+use core::ops::{Add, Sub};
+use riscv::register::{mcycle, minstret};
+
+const MCOUNTINHIBIT_CY: usize = 1 << 0;
+const MCOUNTINHIBIT_IR: usize = 1 << 2;
+
+/// Performance counter
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Counter {
+    /// Clock cycles (`mcycle`)
+    Cycles,
+    /// Retired instructions (`minstret`)
+    Instructions,
+}
+
+impl Counter {
+    /// Reads the 64-bit counter value
+    #[inline]
+    pub fn read(self) -> u64 {
+        match self {
+            Counter::Cycles => mcycle::read64(),
+            Counter::Instructions => minstret::read64(),
+        }
+    }
+
+    /// Sets the counter value
+    pub fn write(self, value: u64) {
+        let (lo, hi) = (value as usize, (value >> 32) as usize);
+        // Clear the low half first so no carry propagates into the new high half
+        // NOTE(unsafe) plain counter CSR writes
+        unsafe {
+            match self {
+                Counter::Cycles => asm!(
+                    "csrw mcycle, zero", "csrw mcycleh, {hi}", "csrw mcycle, {lo}",
+                    hi = in(reg) hi, lo = in(reg) lo,
+                ),
+                Counter::Instructions => asm!(
+                    "csrw minstret, zero", "csrw minstreth, {hi}", "csrw minstret, {lo}",
+                    hi = in(reg) hi, lo = in(reg) lo,
+                ),
+            }
+        }
+    }
+
+    /// Pauses or resumes counting through `mcountinhibit`
+    pub fn set_inhibited(self, inhibited: bool) {
+        let mask = self.inhibit_mask();
+        // NOTE(unsafe) atomic set/clear of this counter's bit in mcountinhibit
+        unsafe {
+            if inhibited {
+                asm!("csrs 0x320, {0}", in(reg) mask);
+            } else {
+                asm!("csrc 0x320, {0}", in(reg) mask);
+            }
+        }
+    }
+
+    /// Returns `true` if counting is paused through `mcountinhibit`
+    pub fn is_inhibited(self) -> bool {
+        let bits: usize;
+        // NOTE(unsafe) read of mcountinhibit
+        unsafe { asm!("csrr {0}, 0x320", out(reg) bits) };
+        bits & self.inhibit_mask() != 0
+    }
+
+    fn inhibit_mask(self) -> usize {
+        match self {
+            Counter::Cycles => MCOUNTINHIBIT_CY,
+            Counter::Instructions => MCOUNTINHIBIT_IR,
+        }
+    }
+}
+
+/// Cycle and instruction counts
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub cycles: u64,
+    pub instructions: u64,
+}
+
+impl Counts {
+    /// Reads both counters
+    #[inline]
+    pub fn now() -> Self {
+        Counts {
+            cycles: Counter::Cycles.read(),
+            instructions: Counter::Instructions.read(),
+        }
+    }
+}
+
+impl Add for Counts {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Counts {
+            cycles: self.cycles + rhs.cycles,
+            instructions: self.instructions + rhs.instructions,
+        }
+    }
+}
+
+impl Sub for Counts {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Counts {
+            cycles: self.cycles.wrapping_sub(rhs.cycles),
+            instructions: self.instructions.wrapping_sub(rhs.instructions),
+        }
+    }
+}
+
+/// Accumulates cycle and instruction counts between `start` and `stop`
+///
+/// Stopwatches only take counter snapshots, so any number of them can run at once.
+#[derive(Copy, Clone)]
+pub struct Stopwatch {
+    hclk: u32,
+    started: Option<Counts>,
+    elapsed: Counts,
+}
+
+impl Stopwatch {
+    /// Creates a stopped stopwatch converting cycles at the HCLK frequency
+    pub fn new(clocks: &Clocks) -> Self {
+        Stopwatch {
+            hclk: clocks.hclk().0,
+            started: None,
+            elapsed: Counts::default(),
+        }
+    }
+
+    /// Returns the frequency `mcycle` counts at
+    pub fn frequency(&self) -> Hertz {
+        Hertz(self.hclk)
+    }
+
+    /// Starts or resumes counting
+    #[inline]
+    pub fn start(&mut self) {
+        if self.started.is_none() {
+            self.started = Some(Counts::now());
+        }
+    }
+
+    /// Stops counting, keeping the elapsed counts
+    #[inline]
+    pub fn stop(&mut self) {
+        if let Some(started) = self.started.take() {
+            self.elapsed = self.elapsed + (Counts::now() - started);
+        }
+    }
+
+    /// Returns `true` if the stopwatch is counting
+    pub fn is_running(&self) -> bool {
+        self.started.is_some()
+    }
+
+    /// Stops the stopwatch and clears the elapsed counts
+    pub fn reset(&mut self) {
+        self.started = None;
+        self.elapsed = Counts::default();
+    }
+
+    /// Returns the counts accumulated so far, including the running interval
+    pub fn elapsed(&self) -> Counts {
+        match self.started {
+            Some(started) => self.elapsed + (Counts::now() - started),
+            None => self.elapsed,
+        }
+    }
+
+    /// Returns the time accumulated so far in microseconds
+    pub fn elapsed_micros(&self) -> u64 {
+        self.to_micros(self.elapsed().cycles)
+    }
+
+    /// Returns the time accumulated so far in nanoseconds
+    pub fn elapsed_nanos(&self) -> u64 {
+        self.to_nanos(self.elapsed().cycles)
+    }
+
+    /// Converts a cycle count to microseconds
+    pub fn to_micros(&self, cycles: u64) -> u64 {
+        (u128::from(cycles) * 1_000_000 / u128::from(self.hclk)) as u64
+    }
+
+    /// Converts a cycle count to nanoseconds
+    pub fn to_nanos(&self, cycles: u64) -> u64 {
+        (u128::from(cycles) * 1_000_000_000 / u128::from(self.hclk)) as u64
+    }
+
+    /// Runs `f` and returns its result with the counts it took
+    ///
+    /// The counts are also added to the stopwatch.
+    pub fn measure<R>(&mut self, f: impl FnOnce() -> R) -> (R, Counts) {
+        let before = self.elapsed();
+        self.start();
+        let result = f();
+        self.stop();
+        (result, self.elapsed() - before)
+    }
+}
+
+/// Counter overflow interrupt (`INT_PMOVI`)
+pub struct OverflowInterrupt;
+
+impl OverflowInterrupt {
+    /// Configures `INT_PMOVI` in the ECLIC with `level` and `priority` and enables it
+    ///
+    /// # Safety
+    ///
+    /// Unmasking `INT_PMOVI` can break mask-based critical sections, as with
+    /// [`EclicExt::unmask`].
+    pub unsafe fn setup(level: Level, priority: Priority) {
+        let mode = VectorMode::NonVectored;
+        ECLIC::setup(Interrupt::INT_PMOVI, TriggerType::RisingEdge, level, priority, mode);
+        unsafe { ECLIC::unmask(Interrupt::INT_PMOVI) };
+    }
+
+    /// Disables `INT_PMOVI`
+    pub fn disable() {
+        ECLIC::mask(Interrupt::INT_PMOVI);
+        ECLIC::unpend(Interrupt::INT_PMOVI);
+    }
+}
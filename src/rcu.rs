@@ -2,9 +2,18 @@
 
 use crate::pac::RCU;
 use riscv::interrupt;
+use crate::gpio::{Active, Alternate, PushPull};
+use crate::gpio::gpioa::PA8;
 use crate::time::Hertz;
 use core::cmp;
 
+const IRC8M: u32 = 8_000_000;
+const IRC40K: u32 = 40_000;
+
+/// Number of polls of HXTALSTB before giving up on the external oscillator
+const HXTAL_STARTUP_TIMEOUT: u32 = 0xFFFF;
+/// Number of polls of PLLSTB, PLL1STB or PLL2STB before giving up on a PLL
+const PLL_LOCK_TIMEOUT: u32 = 0xFFFF;
 
 /// Extension trait that sets up the `RCU` peripheral
 pub trait RcuExt {
@@ -22,27 +31,236 @@ impl RcuExt for RCU {
 pub struct Rcu {
     /// Frozen clock frequencies
     pub clocks: Clocks,
+    reset_reason: ResetReason,
     pub(crate) regs: RCU,
 }
 
+/// Causes of the last reset, as recorded in the RCU_RSTSCK register
+///
+/// Several causes can be reported at once, e.g. a power-on reset also sets the external pin flag.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResetReason(u8);
+
+impl ResetReason {
+    const EPRST: u8 = 1 << 0;
+    const PORRST: u8 = 1 << 1;
+    const SWRST: u8 = 1 << 2;
+    const FWDGTRST: u8 = 1 << 3;
+    const WWDGTRST: u8 = 1 << 4;
+    const LPRST: u8 = 1 << 5;
+
+    /// Reads and clears the reset flags
+    fn take(rcu: &RCU) -> Self {
+        let r = rcu.rstsck.read();
+        let flag = |set: bool, bit: u8| if set { bit } else { 0 };
+        let reason = ResetReason(
+            flag(r.eprstf().bit_is_set(), Self::EPRST)
+                | flag(r.porrstf().bit_is_set(), Self::PORRST)
+                | flag(r.swrstf().bit_is_set(), Self::SWRST)
+                | flag(r.fwdgtrstf().bit_is_set(), Self::FWDGTRST)
+                | flag(r.wwdgtrstf().bit_is_set(), Self::WWDGTRST)
+                | flag(r.lprstf().bit_is_set(), Self::LPRST)
+        );
+
+        interrupt::free(|_| {
+            rcu.rstsck.modify(|_, w| w.rstfc().set_bit());
+        });
+
+        reason
+    }
+
+    /// Reset from the NRST pin
+    pub const fn external_pin(&self) -> bool {
+        self.0 & Self::EPRST != 0
+    }
+
+    /// Power-on or power-down reset
+    pub const fn power_on(&self) -> bool {
+        self.0 & Self::PORRST != 0
+    }
+
+    /// Software reset
+    pub const fn software(&self) -> bool {
+        self.0 & Self::SWRST != 0
+    }
+
+    /// Free watchdog timer reset
+    pub const fn free_watchdog(&self) -> bool {
+        self.0 & Self::FWDGTRST != 0
+    }
+
+    /// Window watchdog timer reset
+    pub const fn window_watchdog(&self) -> bool {
+        self.0 & Self::WWDGTRST != 0
+    }
+
+    /// Low-power management reset (entering deep-sleep or standby when disallowed)
+    pub const fn low_power(&self) -> bool {
+        self.0 & Self::LPRST != 0
+    }
+
+    /// Returns `true` if no reset flag was set
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// Clock routed to the CK_OUT0 pin (PA8)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CkOut0Source {
+    /// No clock output
+    Disabled,
+    /// CK_SYS
+    Sysclk,
+    /// IRC8M
+    Irc8m,
+    /// HXTAL
+    Hxtal,
+    /// CK_PLL / 2
+    PllDiv2,
+    /// CK_PLL1
+    Pll1,
+    /// CK_PLL2 / 2
+    Pll2Div2,
+    /// CK_PLL2
+    Pll2,
+}
+
+impl Rcu {
+    /// Returns the causes of the last reset
+    ///
+    /// The flags are read and cleared when the `RCU` is configured.
+    pub fn reset_reason(&self) -> ResetReason {
+        self.reset_reason
+    }
+
+    /// Configures PA8 as the CK_OUT0 output and routes `source` to it
+    pub fn ckout0<MODE: Active>(
+        &mut self,
+        pa8: PA8<MODE>,
+        source: CkOut0Source
+    ) -> PA8<Alternate<PushPull>> {
+        let pa8 = pa8.into_alternate_push_pull();
+        self.set_ckout0(source);
+        pa8
+    }
+
+    /// Changes the clock routed to CK_OUT0
+    pub fn set_ckout0(&mut self, source: CkOut0Source) {
+        let bits = match source {
+            CkOut0Source::Disabled => 0b0000,
+            CkOut0Source::Sysclk => 0b0100,
+            CkOut0Source::Irc8m => 0b0101,
+            CkOut0Source::Hxtal => 0b0110,
+            CkOut0Source::PllDiv2 => 0b0111,
+            CkOut0Source::Pll1 => 0b1000,
+            CkOut0Source::Pll2Div2 => 0b1001,
+            CkOut0Source::Pll2 => 0b1011,
+        };
+
+        interrupt::free(|_| {
+            self.regs.cfg0.modify(|_, w| unsafe { w.ckout0sel().bits(bits) });
+        });
+    }
+
+    /// Enables the HXTAL clock monitor (CKM)
+    ///
+    /// When HXTAL fails, the hardware switches CK_SYS to IRC8M, disables the PLLs fed from
+    /// HXTAL and raises a non-maskable interrupt. The handler can check for the failure with
+    /// `clock_failure_pending` and must acknowledge it with `clear_clock_failure`. Afterwards,
+    /// `refresh_clocks` updates `clocks` so drivers can be reconfigured.
+    pub fn enable_clock_monitor(&mut self) {
+        interrupt::free(|_| {
+            self.regs.ctl.modify(|_, w| w.ckmen().set_bit());
+        });
+    }
+
+    /// Disables the HXTAL clock monitor
+    pub fn disable_clock_monitor(&mut self) {
+        interrupt::free(|_| {
+            self.regs.ctl.modify(|_, w| w.ckmen().clear_bit());
+        });
+    }
+
+    /// `true` if the clock monitor detected an HXTAL failure
+    #[inline]
+    pub fn clock_failure_pending() -> bool {
+        // NOTE(unsafe) atomic read with no side effects
+        unsafe { (*RCU::ptr()).int.read().ckmif().bit_is_set() }
+    }
+
+    /// Clears the clock monitor interrupt flag
+    #[inline]
+    pub fn clear_clock_failure() {
+        // NOTE(unsafe) the other clear bits read as zero, so only CKMIF is acknowledged
+        unsafe { (*RCU::ptr()).int.modify(|_, w| w.ckmic().set_bit()) };
+    }
+
+    /// Updates `clocks` with the clock tree actually in use and returns it
+    ///
+    /// After a clock monitor failover, CK_SYS runs from IRC8M, the bus prescalers are kept and
+    /// the PLLs fed from HXTAL are stopped.
+    pub fn refresh_clocks(&mut self) -> Clocks {
+        let ctl = self.regs.ctl.read();
+
+        if self.regs.cfg0.read().scss().bits() == 0b00 {
+            self.clocks.sysclk = Hertz(IRC8M);
+            self.clocks.usbclk_valid = false;
+        }
+        if ctl.hxtalen().bit_is_clear() || ctl.pll1stb().bit_is_clear() {
+            self.clocks.pll1clk = None;
+        }
+        if ctl.hxtalen().bit_is_clear() || ctl.pll2stb().bit_is_clear() {
+            self.clocks.pll2clk = None;
+        }
+
+        self.clocks
+    }
+}
+
 pub struct UnconfiguredRcu {
+    reset_reason: ResetReason,
     hxtal: Option<u32>,
+    predv1: u8,
+    pll1mf: Option<u8>,
+    pll2mf: Option<u8>,
+    i2s1_pll2: bool,
+    i2s2_pll2: bool,
     sysclk: Option<u32>,
+    hclk: Option<u32>,
+    pclk1: Option<u32>,
+    pclk2: Option<u32>,
     regs: RCU,
 }
 
 impl UnconfiguredRcu {
     fn new(rcu: RCU) -> Self {
         Self {
+            reset_reason: ResetReason::take(&rcu),
             hxtal: None,
+            predv1: 1,
+            pll1mf: None,
+            pll2mf: None,
+            i2s1_pll2: false,
+            i2s2_pll2: false,
             sysclk: None,
+            hclk: None,
+            pclk1: None,
+            pclk2: None,
             regs: rcu,
         }
     }
 
+    /// Returns the causes of the last reset
+    ///
+    /// The flags are read and cleared when the `RCU` is configured.
+    pub fn reset_reason(&self) -> ResetReason {
+        self.reset_reason
+    }
+
     /// Uses an external oscillator instead of IRC8M (internal RC oscillator) as the high-speed
-    /// clock source. Will result in a hang if an external oscillator is not connected or it fails
-    /// to start.
+    /// clock source. `freeze` will panic if an external oscillator is not connected or it fails
+    /// to start, use `try_freeze` to recover from that.
     pub fn ext_hf_clock(mut self, freq: impl Into<Hertz>) -> Self {
         let freq = freq.into().0;
         assert!(4_000_000 <= freq && freq <= 32_000_000);
@@ -51,6 +269,46 @@ impl UnconfiguredRcu {
         self
     }
 
+    /// Sets the PREDV1 division factor (1..=16) between HXTAL and PLL1/PLL2
+    pub fn predv1(mut self, div: u8) -> Self {
+        assert!((1..=16).contains(&div));
+
+        self.predv1 = div;
+        self
+    }
+
+    /// Enables PLL1 with the multiplication factor `mf` and uses its output instead of HXTAL as
+    /// the PREDV0 input. Valid factors are 8..=14, 16 and 20.
+    ///
+    /// This allows crystals like 25 MHz to reach exact SYSCLK frequencies together with a valid
+    /// USB clock, e.g. 25 MHz / 5 * 8 / 10 * 24 = 96 MHz.
+    pub fn pll1(mut self, mf: u8) -> Self {
+        assert!(pll12mf_bits(mf).is_some());
+
+        self.pll1mf = Some(mf);
+        self
+    }
+
+    /// Enables PLL2 with the multiplication factor `mf`. Valid factors are 8..=14, 16 and 20.
+    pub fn pll2(mut self, mf: u8) -> Self {
+        assert!(pll12mf_bits(mf).is_some());
+
+        self.pll2mf = Some(mf);
+        self
+    }
+
+    /// Clocks I2S1 (SPI1) from CK_PLL2 * 2 instead of CK_SYS
+    pub fn i2s1_from_pll2(mut self) -> Self {
+        self.i2s1_pll2 = true;
+        self
+    }
+
+    /// Clocks I2S2 (SPI2) from CK_PLL2 * 2 instead of CK_SYS
+    pub fn i2s2_from_pll2(mut self) -> Self {
+        self.i2s2_pll2 = true;
+        self
+    }
+
     /// Sets the desired frequency for the SYSCLK clock
     pub fn sysclk(mut self, freq: impl Into<Hertz>) -> Self {
         let freq = freq.into().0;
@@ -60,10 +318,55 @@ impl UnconfiguredRcu {
         self
     }
 
+    /// Sets the desired frequency for the HCLK (AHB) clock
+    ///
+    /// The AHB prescaler is chosen so that HCLK does not exceed `freq`. Defaults to SYSCLK.
+    pub fn hclk(mut self, freq: impl Into<Hertz>) -> Self {
+        let freq = freq.into().0;
+        assert!(0 < freq && freq <= 108_000_000);
+
+        self.hclk = Some(freq);
+        self
+    }
+
+    /// Sets the desired frequency for the PCLK1 (APB1) clock
+    ///
+    /// The APB1 prescaler is chosen so that PCLK1 does not exceed `freq`. Defaults to HCLK / 2.
+    pub fn pclk1(mut self, freq: impl Into<Hertz>) -> Self {
+        let freq = freq.into().0;
+        assert!(0 < freq && freq <= 54_000_000);
+
+        self.pclk1 = Some(freq);
+        self
+    }
+
+    /// Sets the desired frequency for the PCLK2 (APB2) clock
+    ///
+    /// The APB2 prescaler is chosen so that PCLK2 does not exceed `freq`. Defaults to HCLK.
+    pub fn pclk2(mut self, freq: impl Into<Hertz>) -> Self {
+        let freq = freq.into().0;
+        assert!(0 < freq && freq <= 108_000_000);
+
+        self.pclk2 = Some(freq);
+        self
+    }
+
     /// Freezes clock configuration, making it effective
+    ///
+    /// Panics if the configuration can't be applied, see `try_freeze` for details.
     pub fn freeze(self) -> Rcu {
-        const IRC8M: u32 = 8_000_000;
+        match self.try_freeze() {
+            Ok(rcu) => rcu,
+            Err((err, _)) => panic!("clock configuration failed: {:?}", err),
+        }
+    }
 
+    /// Freezes clock configuration, making it effective
+    ///
+    /// If the requested SYSCLK can't be reached, or HXTAL or one of the PLLs fail to become
+    /// stable in time, the clock tree is left running from IRC8M at 8 MHz and the error is returned
+    /// together with an `Rcu` describing that fallback configuration.
+    pub fn try_freeze(self) -> Result<Rcu, (ClockError, Rcu)> {
         let target_sysclk = self.sysclk.unwrap_or(IRC8M);
 
         let (scs_bits, use_pll) = match (self.hxtal, target_sysclk) {
@@ -72,92 +375,140 @@ impl UnconfiguredRcu {
             _ => (0b10, true),
         };
 
-        let pllsel_bit;
-        let predv0_bits;
-        let pllmf_bits;
-        if use_pll {
-            let pllmf;
-
-            if let Some(hxtal_freq) = self.hxtal {
-                // Use external clock + divider
-                pllsel_bit = true;
-
-                let calculate_pll = |source: u32, target: u32| -> Option<(u8, u8)> {
-                    const PLL_IN_MIN: u32 = 600_000;
-                    let div_max = cmp::min(16, source / PLL_IN_MIN);
-
-                    for d in 1..=div_max {
-                        let pllsource = source / d;
-                        let pllm = target / pllsource;
-                        if pllm < 2 || pllm == 15 || pllm > 32{
-                            continue;
-                        }
-                        let actual_freq = pllsource * pllm;
-                        if actual_freq == target {
-                            return Some((d as u8, pllm as u8));
-                        }
-                    }
-                    None
-                };
-
-                let (d, m) = calculate_pll(hxtal_freq, target_sysclk).expect("invalid sysclk value");
-                predv0_bits = d - 1;
-                pllmf = m;
-            } else {
-                // IRC8M/2 is used as an input clock
-                pllsel_bit = false;
-
-                let pllsource = IRC8M / 2;
-                let m = target_sysclk / pllsource;
-                let m = cmp::max(2, cmp::min(m, 32));
-                assert_ne!(m, 15, "invalid sysclk value");
-                let actual_sysclk = pllsource * m;
-                assert_eq!(target_sysclk, actual_sysclk, "invalid sysclk value");
-
-                predv0_bits = 0;
-                pllmf = m as u8;
-            }
-
-            pllmf_bits = match pllmf {
-                2..=14 => pllmf - 2,
-                16..=32 => pllmf - 1,
-                _ => unreachable!("invalid pll multiplier"),
-            };
+        // PLL1 and PLL2 are fed from HXTAL through PREDV1
+        let pll12_in = self.hxtal.map(|hxtal| hxtal / self.predv1 as u32);
+        let pll1clk = self.pll1mf.and_then(|mf| pll12_in.map(|f| f * mf as u32));
+        let pll2clk = self.pll2mf.and_then(|mf| pll12_in.map(|f| f * mf as u32));
+        let pll12_valid = (self.pll1mf.is_none() || pll1clk.is_some())
+            && (self.pll2mf.is_none() || pll2clk.is_some())
+            && (!(self.i2s1_pll2 || self.i2s2_pll2) || pll2clk.is_some());
+
+        // PREDV0 is fed either from HXTAL or from PLL1
+        let predv0_in = if self.pll1mf.is_some() { pll1clk } else { self.hxtal };
+
+        let pll = if !pll12_valid {
+            Err(ClockError::NoPllSolution)
+        } else if use_pll {
+            calculate_pll(predv0_in, target_sysclk).ok_or(ClockError::NoPllSolution)
         } else {
-            pllsel_bit = false;
-            predv0_bits = 0;
-            pllmf_bits = 0;
-        }
+            Ok(PllConfig { predv0: 1, pllmf: 2 })
+        };
+
+        // Smallest divider that keeps the bus clocks at or below their targets
+        let (ahbpsc_bits, ahb_psc) = match self.hclk.map(|hclk| target_sysclk.div_ceil(hclk)) {
+            None | Some(0..=1) => (0b0000, 1),
+            Some(2) => (0b1000, 2),
+            Some(3..=4) => (0b1001, 4),
+            Some(5..=8) => (0b1010, 8),
+            Some(9..=16) => (0b1011, 16),
+            Some(17..=64) => (0b1100, 64),
+            Some(65..=128) => (0b1101, 128),
+            Some(129..=256) => (0b1110, 256),
+            Some(_) => (0b1111, 512),
+        };
+        let hclk = target_sysclk / ahb_psc;
+
+        let apb_psc = |pclk: Option<u32>, default: u32| -> (u8, u8) {
+            match pclk.map_or(default, |pclk| hclk.div_ceil(pclk)) {
+                0..=1 => (0b000, 1),
+                2 => (0b100, 2),
+                3..=4 => (0b101, 4),
+                5..=8 => (0b110, 8),
+                _ => (0b111, 16),
+            }
+        };
+        let (apb1psc_bits, apb1_psc) = apb_psc(self.pclk1, 2);
+        let (apb2psc_bits, apb2_psc) = apb_psc(self.pclk2, 1);
 
         // Switch to the internal clock
         let rcu = unsafe { &*crate::pac::RCU::ptr() };
         rcu.ctl.modify(|_, w| w.irc8men().set_bit()); // Enable IRC8M oscillator
         while rcu.ctl.read().irc8mstb().bit_is_clear() {} // Wait for oscillator to stabilize
         rcu.cfg0.modify(|_, w| unsafe { w.scs().bits(0b00) }); // Switch to the internal oscillator
-        rcu.ctl.modify(|_, w| w.pllen().clear_bit()); // Disable PLL
+        rcu.ctl.modify(|_, w| w.pllen().clear_bit().pll1en().clear_bit().pll2en().clear_bit()); // Disable PLLs
+        rcu.cfg1.modify(|_, w| w.i2s1sel().clear_bit().i2s2sel().clear_bit()); // I2S clocks from CK_SYS
 
         // Set bus prescalers
-        rcu.cfg0.modify(|_, w| unsafe { w.ahbpsc().bits(0b0000) }); // CK_SYS
-        rcu.cfg0.modify(|_, w| unsafe { w.apb1psc().bits(0b100) }); // CK_AHB / 2
-        rcu.cfg0.modify(|_, w| unsafe { w.apb2psc().bits(0b000) }); // CK_AHB
-        let apb1_psc = 2;
-        let apb2_psc = 1;
+        rcu.cfg0.modify(|_, w| unsafe { w.ahbpsc().bits(ahbpsc_bits) }); // CK_SYS / ahb_psc
+        rcu.cfg0.modify(|_, w| unsafe { w.apb1psc().bits(apb1psc_bits) }); // CK_AHB / apb1_psc
+        rcu.cfg0.modify(|_, w| unsafe { w.apb2psc().bits(apb2psc_bits) }); // CK_AHB / apb2_psc
+
+        // Clock tree left behind if anything goes wrong from here on
+        let fallback = Clocks {
+            sysclk: Hertz(IRC8M),
+            ahb_psc: ahb_psc as u16,
+            apb1_psc,
+            apb2_psc,
+            usbclk_valid: false,
+            pll1clk: None,
+            pll2clk: None,
+            i2s1_pll2: false,
+            i2s2_pll2: false,
+            irc40k: Hertz(IRC40K),
+        };
+        let shutdown = || rcu.ctl.modify(|_, w| w
+            .pllen().clear_bit()
+            .pll1en().clear_bit()
+            .pll2en().clear_bit()
+            .hxtalen().clear_bit()
+        );
+
+        let pll = match pll {
+            Ok(pll) => pll,
+            Err(err) => return Err((err, self.into_rcu(fallback))),
+        };
+        let predv0_bits = pll.predv0 - 1;
+        let pllmf_bits = match pll.pllmf {
+            2..=14 => pll.pllmf - 2,
+            16..=32 => pll.pllmf - 1,
+            _ => unreachable!("invalid pll multiplier"),
+        };
 
         if self.hxtal.is_some() {
             // Enable external oscillator
             rcu.ctl.modify(|_, w| w.hxtalen().set_bit());
             // Wait for oscillator to stabilize
-            while rcu.ctl.read().hxtalstb().bit_is_clear() {}
+            if !wait_for(HXTAL_STARTUP_TIMEOUT, || rcu.ctl.read().hxtalstb().bit_is_set()) {
+                shutdown();
+                return Err((ClockError::HxtalTimeout, self.into_rcu(fallback)));
+            }
+
+            // Configure PREDV1 and the PLL1/PLL2 multipliers
+            let pll1mf_bits = self.pll1mf.and_then(pll12mf_bits).unwrap_or(0);
+            let pll2mf_bits = self.pll2mf.and_then(pll12mf_bits).unwrap_or(0);
+            rcu.cfg1.modify(|_, w| unsafe { w
+                .predv1().bits(self.predv1 - 1)
+                .pll1mf().bits(pll1mf_bits)
+                .pll2mf().bits(pll2mf_bits)
+            });
 
-            // Select HXTAL as prescaler input source clock
-            rcu.cfg1.modify(|_, w| w.predv0sel().clear_bit());
+            if self.pll1mf.is_some() {
+                // Enable PLL1 and wait for it to stabilize
+                rcu.ctl.modify(|_, w| w.pll1en().set_bit());
+                if !wait_for(PLL_LOCK_TIMEOUT, || rcu.ctl.read().pll1stb().bit_is_set()) {
+                    shutdown();
+                    return Err((ClockError::Pll1LockTimeout, self.into_rcu(fallback)));
+                }
+            }
+
+            if self.pll2mf.is_some() {
+                // Enable PLL2 and wait for it to stabilize
+                rcu.ctl.modify(|_, w| w.pll2en().set_bit());
+                if !wait_for(PLL_LOCK_TIMEOUT, || rcu.ctl.read().pll2stb().bit_is_set()) {
+                    shutdown();
+                    return Err((ClockError::Pll2LockTimeout, self.into_rcu(fallback)));
+                }
+            }
+
+            // Select HXTAL or PLL1 as prescaler input source clock
+            rcu.cfg1.modify(|_, w| w.predv0sel().bit(self.pll1mf.is_some()));
             // Configure the prescaler
             rcu.cfg1.modify(|_, w| unsafe { w.predv0().bits(predv0_bits) });
         }
 
         if use_pll {
             // Configure PLL input selector
-            rcu.cfg0.modify(|_, w| w.pllsel().bit(pllsel_bit));
+            rcu.cfg0.modify(|_, w| w.pllsel().bit(self.hxtal.is_some()));
             // Configure PLL multiplier
             rcu.cfg0.modify(|_, w| unsafe { w
                 .pllmf_4().bit(pllmf_bits & 0x10 != 0)
@@ -166,12 +517,18 @@ impl UnconfiguredRcu {
             // Enable PLL
             rcu.ctl.modify(|_, w| w.pllen().set_bit());
             // Wait for PLL to stabilize
-            while rcu.ctl.read().pllstb().bit_is_clear() {}
+            if !wait_for(PLL_LOCK_TIMEOUT, || rcu.ctl.read().pllstb().bit_is_set()) {
+                shutdown();
+                return Err((ClockError::PllLockTimeout, self.into_rcu(fallback)));
+            }
         } else {
             // Disable PLL
             rcu.ctl.modify(|_, w| w.pllen().clear_bit());
         }
 
+        // Select the I2S clock sources
+        rcu.cfg1.modify(|_, w| w.i2s1sel().bit(self.i2s1_pll2).i2s2sel().bit(self.i2s2_pll2));
+
         // Switch to the configured clock source
         rcu.cfg0.modify(|_, w| unsafe { w.scs().bits(scs_bits) });
 
@@ -194,24 +551,121 @@ impl UnconfiguredRcu {
 
         let clocks = Clocks {
             sysclk: Hertz(target_sysclk),
+            ahb_psc: ahb_psc as u16,
             apb1_psc,
             apb2_psc,
-            usbclk_valid
+            usbclk_valid,
+            pll1clk: pll1clk.map(Hertz),
+            pll2clk: pll2clk.map(Hertz),
+            i2s1_pll2: self.i2s1_pll2,
+            i2s2_pll2: self.i2s2_pll2,
+            irc40k: Hertz(IRC40K),
         };
 
+        Ok(self.into_rcu(clocks))
+    }
+
+    fn into_rcu(self, clocks: Clocks) -> Rcu {
         Rcu {
             clocks,
+            reset_reason: self.reset_reason,
             regs: self.regs
         }
     }
 }
 
+/// Returns the PLL1MF/PLL2MF bits for the multiplication factor `mf`
+fn pll12mf_bits(mf: u8) -> Option<u8> {
+    match mf {
+        8..=14 => Some(mf - 2),
+        16 => Some(0b1110),
+        20 => Some(0b1111),
+        _ => None,
+    }
+}
+
+/// Polls `ready` until it returns `true`, giving up after `timeout` attempts
+fn wait_for(timeout: u32, ready: impl Fn() -> bool) -> bool {
+    (0..timeout).any(|_| ready())
+}
+
+/// Clock configuration error
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClockError {
+    /// The requested SYSCLK frequency can't be produced by the PLL, or PLL1/PLL2 were requested
+    /// without HXTAL
+    NoPllSolution,
+    /// HXTAL did not become stable in time
+    HxtalTimeout,
+    /// The PLL did not lock in time
+    PllLockTimeout,
+    /// PLL1 did not lock in time
+    Pll1LockTimeout,
+    /// PLL2 did not lock in time
+    Pll2LockTimeout,
+}
+
+/// PLL dividers and multipliers producing a requested frequency
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PllConfig {
+    /// PREDV0 division factor (1..=16). Always 1 when the PLL is fed from IRC8M / 2
+    pub predv0: u8,
+    /// PLL multiplication factor (2..=14, 16..=32)
+    pub pllmf: u8,
+}
+
+/// Searches for a PLL configuration producing exactly `target` Hz
+///
+/// The PLL is fed through PREDV0 from `predv0_in` (HXTAL or CK_PLL1) or, if `predv0_in` is
+/// `None`, from IRC8M / 2. This function has no side effects.
+pub fn calculate_pll(predv0_in: Option<u32>, target: u32) -> Option<PllConfig> {
+    const PLL_IN_MIN: u32 = 600_000;
+
+    let valid_pllmf = |m: u32| (2..=32).contains(&m) && m != 15;
+
+    match predv0_in {
+        Some(source) => {
+            // Use external clock + divider
+            let div_max = cmp::min(16, source / PLL_IN_MIN);
+
+            for d in 1..=div_max {
+                let pllsource = source / d;
+                let pllm = target / pllsource;
+                if !valid_pllmf(pllm) {
+                    continue;
+                }
+                let actual_freq = pllsource * pllm;
+                if actual_freq == target {
+                    return Some(PllConfig { predv0: d as u8, pllmf: pllm as u8 });
+                }
+            }
+            None
+        }
+        None => {
+            // IRC8M/2 is used as an input clock
+            let pllsource = IRC8M / 2;
+            let m = target / pllsource;
+            if valid_pllmf(m) && pllsource * m == target {
+                Some(PllConfig { predv0: 1, pllmf: m as u8 })
+            } else {
+                None
+            }
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct Clocks {
     sysclk: Hertz,
+    ahb_psc: u16,
     apb1_psc: u8,
     apb2_psc: u8,
     usbclk_valid: bool,
+    pll1clk: Option<Hertz>,
+    pll2clk: Option<Hertz>,
+    i2s1_pll2: bool,
+    i2s2_pll2: bool,
+    pub(crate) irc40k: Hertz,
 }
 
 impl Clocks {
@@ -222,22 +676,37 @@ impl Clocks {
 
     /// Returns the frequency of the AHB
     pub const fn hclk(&self) -> Hertz {
-        self.sysclk
+        Hertz(self.sysclk.0 / self.ahb_psc as u32)
+    }
+
+    /// Returns the AHB prescaler value
+    pub const fn ahb_psc(&self) -> u16 {
+        self.ahb_psc
     }
 
     /// Returns the frequency of the APB1
     pub const fn pclk1(&self) -> Hertz {
-        Hertz(self.sysclk.0 / self.apb1_psc as u32)
+        Hertz(self.hclk().0 / self.apb1_psc as u32)
+    }
+
+    /// Returns the APB1 prescaler value
+    pub const fn apb1_psc(&self) -> u8 {
+        self.apb1_psc
     }
 
     /// Returns the frequency of the APB2
     pub const fn pclk2(&self) -> Hertz {
-        Hertz(self.sysclk.0 / self.apb2_psc as u32)
+        Hertz(self.hclk().0 / self.apb2_psc as u32)
+    }
+
+    /// Returns the APB2 prescaler value
+    pub const fn apb2_psc(&self) -> u8 {
+        self.apb2_psc
     }
 
     /// Returns the frequency of the SysTick timer
     pub const fn systick(&self) -> Hertz {
-        Hertz(self.sysclk.0 / 4)
+        Hertz(self.hclk().0 / 4)
     }
 
     /// Returns the frequency of the TIMER0 base clock
@@ -264,6 +733,38 @@ impl Clocks {
     pub const fn usbclk_valid(&self) -> bool {
         self.usbclk_valid
     }
+
+    /// Returns the frequency of PLL1, if enabled
+    pub const fn pll1clk(&self) -> Option<Hertz> {
+        self.pll1clk
+    }
+
+    /// Returns the frequency of PLL2, if enabled
+    pub const fn pll2clk(&self) -> Option<Hertz> {
+        self.pll2clk
+    }
+
+    /// Returns the frequency of the I2S1 (SPI1) clock
+    pub fn i2s1clk(&self) -> Hertz {
+        match self.pll2clk {
+            Some(pll2clk) if self.i2s1_pll2 => Hertz(pll2clk.0 * 2),
+            _ => self.sysclk,
+        }
+    }
+
+    /// Returns the frequency of the I2S2 (SPI2) clock
+    pub fn i2s2clk(&self) -> Hertz {
+        match self.pll2clk {
+            Some(pll2clk) if self.i2s2_pll2 => Hertz(pll2clk.0 * 2),
+            _ => self.sysclk,
+        }
+    }
+
+    /// Returns the frequency of IRC40K, nominal unless measured by
+    /// [`calibrate`](crate::calibration::calibrate)
+    pub const fn irc40k(&self) -> Hertz {
+        self.irc40k
+    }
 }
 
 macro_rules! base_freq {
@@ -305,25 +806,88 @@ pub(crate) mod closed_traits {
     use super::Rcu;
     use crate::time::Hertz;
 
+    /// Prevents the traits below from being implemented outside of this crate
+    pub trait Sealed {}
+
     /// Enable/disable peripheral
-    pub trait Enable {
+    pub trait Enable: Sealed {
+        /// Enables the peripheral clock
         fn enable(rcu: &mut Rcu);
+        /// Disables the peripheral clock
         fn disable(rcu: &mut Rcu);
+        /// Returns `true` if the peripheral clock is enabled
+        fn is_enabled(rcu: &Rcu) -> bool;
     }
 
     /// Reset peripheral
-    pub trait Reset {
+    pub trait Reset: Sealed {
+        /// Pulses the peripheral reset
         fn reset(rcu: &mut Rcu);
     }
 
-    pub trait BaseFrequency {
+    /// Peripheral base (kernel) clock frequency
+    pub trait BaseFrequency: Sealed {
+        /// Returns the frequency of the clock feeding the peripheral
         fn base_frequency(rcu: &Rcu) -> Hertz;
     }
 }
-pub(crate) use closed_traits::*;
+pub use closed_traits::{BaseFrequency, Enable, Reset};
+pub(crate) use closed_traits::Sealed;
+
+/// Snapshot of the peripheral clock enable registers
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PeripheralClocks {
+    /// Raw value of RCU_AHBEN
+    pub ahben: u32,
+    /// Raw value of RCU_APB1EN
+    pub apb1en: u32,
+    /// Raw value of RCU_APB2EN
+    pub apb2en: u32,
+}
+
+impl Rcu {
+    /// Enables the clock of the peripheral `PER`
+    ///
+    /// This is meant for peripherals without a driver in this crate, e.g.
+    /// `rcu.enable::<pac::CAN0>()`.
+    pub fn enable<PER: Enable>(&mut self) {
+        PER::enable(self);
+    }
+
+    /// Disables the clock of the peripheral `PER`
+    pub fn disable<PER: Enable>(&mut self) {
+        PER::disable(self);
+    }
+
+    /// Returns `true` if the clock of the peripheral `PER` is enabled
+    pub fn is_enabled<PER: Enable>(&self) -> bool {
+        PER::is_enabled(self)
+    }
+
+    /// Resets the peripheral `PER`
+    pub fn reset<PER: Reset>(&mut self) {
+        PER::reset(self);
+    }
+
+    /// Returns the base clock frequency of the peripheral `PER`
+    pub fn base_frequency<PER: BaseFrequency>(&self) -> Hertz {
+        PER::base_frequency(self)
+    }
+
+    /// Returns which peripheral clocks are currently enabled
+    pub fn peripheral_clocks(&self) -> PeripheralClocks {
+        PeripheralClocks {
+            ahben: self.regs.ahben.read().bits(),
+            apb1en: self.regs.apb1en.read().bits(),
+            apb2en: self.regs.apb2en.read().bits(),
+        }
+    }
+}
 
 macro_rules! bus_enable {
     ($PER:ident => ($apben:ident, $peren:ident)) => {
+        impl Sealed for crate::pac::$PER {}
+
         impl Enable for crate::pac::$PER {
             #[inline(always)]
             fn enable(rcu: &mut Rcu) {
@@ -338,6 +902,11 @@ macro_rules! bus_enable {
                     rcu.regs.$apben.modify(|_, w| w.$peren().clear_bit());
                 });
             }
+
+            #[inline(always)]
+            fn is_enabled(rcu: &Rcu) -> bool {
+                rcu.regs.$apben.read().$peren().bit_is_set()
+            }
         }
     }
 }
@@ -398,3 +967,50 @@ bus_enable!(CRC => (ahben, crcen));
 bus_enable!(DMA0 => (ahben, dma0en));
 bus_enable!(DMA1 => (ahben, dma1en));
 bus_enable!(EXMC => (ahben, exmcen));
+
+#[cfg(test)]
+mod tests {
+    use super::{calculate_pll, PllConfig};
+
+    #[test]
+    fn pll_from_hxtal() {
+        assert_eq!(
+            calculate_pll(Some(8_000_000), 108_000_000),
+            Some(PllConfig { predv0: 2, pllmf: 27 })
+        );
+        assert_eq!(
+            calculate_pll(Some(25_000_000), 100_000_000),
+            Some(PllConfig { predv0: 1, pllmf: 4 })
+        );
+    }
+
+    #[test]
+    fn pll_from_irc8m() {
+        assert_eq!(
+            calculate_pll(None, 108_000_000),
+            Some(PllConfig { predv0: 1, pllmf: 27 })
+        );
+        assert_eq!(
+            calculate_pll(None, 8_000_000),
+            Some(PllConfig { predv0: 1, pllmf: 2 })
+        );
+    }
+
+    #[test]
+    fn pll_skips_multiplier_15() {
+        // 8 MHz x 15 is forbidden, so the input is divided by two first
+        assert_eq!(
+            calculate_pll(Some(8_000_000), 120_000_000),
+            Some(PllConfig { predv0: 2, pllmf: 30 })
+        );
+        assert_eq!(calculate_pll(None, 60_000_000), None);
+    }
+
+    #[test]
+    fn pll_unreachable_targets() {
+        assert_eq!(calculate_pll(Some(8_000_000), 109_000_000), None);
+        assert_eq!(calculate_pll(Some(8_000_000), 1_000_000), None);
+        assert_eq!(calculate_pll(None, 6_000_000), None);
+        assert_eq!(calculate_pll(None, 200_000_000), None);
+    }
+}
@@ -27,6 +27,8 @@ pub struct Rtc {
     /// ensure that LXTAL is configured as long as RTC exists
     #[allow(dead_code)]
     lxtal: Lxtal,
+    /// Prescaler value, PSCH/PSCL are write-only
+    prescaler: u32,
 }
 
 impl Rtc {
@@ -34,13 +36,14 @@ impl Rtc {
       Initialises the RTC.
     */
     pub fn rtc(regs: RTC, bkp: &mut BackupDomain, lxtal: Lxtal) -> Self {
-        let mut result = Rtc { regs, lxtal };
+        let mut result = Rtc { regs, lxtal, prescaler: 0 };
 
         Rtc::enable_rtc(bkp, &lxtal);
 
         // Set the prescaler to make it count up once every second.
         let prl = LXTAL_HERTZ - 1;
         assert!(prl < 1 << 20);
+        result.prescaler = prl;
         result.perform_write(|s| {
             s.regs.psch.write(|w| unsafe { w.bits(prl >> 16) });
             s.regs.pscl.write(|w| unsafe { w.bits(prl as u16 as u32) });
@@ -73,6 +76,7 @@ impl Rtc {
         assert!(frequency <= LXTAL_HERTZ);
 
         let prescaler = LXTAL_HERTZ / frequency - 1;
+        self.prescaler = prescaler;
         self.perform_write(|s| {
             s.regs.psch.write(|w| unsafe { w.bits(prescaler >> 16) });
             s.regs
@@ -139,6 +143,23 @@ impl Rtc {
         self.regs.cnth.read().bits() << 16 | self.regs.cntl.read().bits()
     }
 
+    /// Returns the prescaler value, the divider reloads from it every `prescaler + 1` LXTAL periods
+    pub(crate) fn prescaler(&self) -> u32 {
+        self.prescaler
+    }
+
+    /// Returns `true` once the APB1 interface is synchronized and registers can be read
+    pub(crate) fn is_synchronized(&self) -> bool {
+        self.regs.ctl.read().rsynf().bit()
+    }
+
+    /// Reads the low half of the prescaler divider, which counts down once per LXTAL period
+    ///
+    /// Only valid once [`is_synchronized`](Self::is_synchronized) returns `true`.
+    pub(crate) fn divider_low(&self) -> u16 {
+        self.regs.divl.read().div().bits()
+    }
+
     /// Enables the RTC second interrupt.
     /// This interrupt triggers whenever the RTC counter increases.
     pub fn listen_seconds(&mut self) {
@@ -42,6 +42,7 @@
 
 use core::marker::PhantomData;
 use core::ptr;
+use core::sync::atomic::{self, Ordering};
 
 use nb;
 use core::convert::Infallible;
@@ -50,6 +51,12 @@ use embedded_hal::serial::Write;
 use crate::rcu::Rcu;
 use crate::time::{U32Ext, Bps};
 use crate::afio::Afio;
+use crate::dma::{
+    dma0, ChannelConfig, CircBuffer, CircReadDma, Priority, ReadDma, Receive, RxDma, Transfer,
+    TransferPayload, Transmit, TxDma, WriteDma, R, W,
+};
+use crate::pac::{USART0, USART1, USART2};
+use embedded_dma::{StaticReadBuffer, StaticWriteBuffer};
 
 /// Interrupt event
 pub enum Event {
@@ -460,3 +467,179 @@ where
             .map_err(|_| core::fmt::Error)
     }
 }
+
+/// USART0 receiver on DMA0 channel 4
+pub type RxDma0 = RxDma<Rx<USART0>, dma0::C4>;
+/// USART1 receiver on DMA0 channel 5
+pub type RxDma1 = RxDma<Rx<USART1>, dma0::C5>;
+/// USART2 receiver on DMA0 channel 2
+pub type RxDma2 = RxDma<Rx<USART2>, dma0::C2>;
+
+/// USART0 transmitter on DMA0 channel 3
+pub type TxDma0 = TxDma<Tx<USART0>, dma0::C3>;
+/// USART1 transmitter on DMA0 channel 6
+pub type TxDma1 = TxDma<Tx<USART1>, dma0::C6>;
+/// USART2 transmitter on DMA0 channel 1
+pub type TxDma2 = TxDma<Tx<USART2>, dma0::C1>;
+
+macro_rules! serialdma {
+    ($(
+        $USARTX:ident: (
+            $rxdma:ident,
+            $txdma:ident,
+            $dmarxch:ty,
+            $dmatxch:ty
+        ),
+    )+) => {
+        $(
+            impl Receive for $rxdma {
+                type RxChannel = $dmarxch;
+                type TransmittedWord = u8;
+            }
+
+            impl Transmit for $txdma {
+                type TxChannel = $dmatxch;
+                type ReceivedWord = u8;
+            }
+
+            impl TransferPayload for $rxdma {
+                fn start(&mut self) {
+                    self.channel.start();
+                }
+                fn stop(&mut self) {
+                    self.channel.stop();
+                }
+            }
+
+            impl TransferPayload for $txdma {
+                fn start(&mut self) {
+                    self.channel.start();
+                }
+                fn stop(&mut self) {
+                    self.channel.stop();
+                }
+            }
+
+            impl Rx<$USARTX> {
+                /// Attaches the receive DMA channel of this USART
+                pub fn with_dma(self, channel: $dmarxch) -> $rxdma {
+                    RxDma { payload: self, channel }
+                }
+            }
+
+            impl Tx<$USARTX> {
+                /// Attaches the transmit DMA channel of this USART
+                pub fn with_dma(self, channel: $dmatxch) -> $txdma {
+                    TxDma { payload: self, channel }
+                }
+            }
+
+            impl $rxdma {
+                /// Stops any transfer and returns the receiver and the DMA channel
+                pub fn split(mut self) -> (Rx<$USARTX>, $dmarxch) {
+                    self.stop();
+                    let RxDma { payload, channel } = self;
+                    (payload, channel)
+                }
+            }
+
+            impl $txdma {
+                /// Stops any transfer and returns the transmitter and the DMA channel
+                pub fn split(mut self) -> (Tx<$USARTX>, $dmatxch) {
+                    self.stop();
+                    let TxDma { payload, channel } = self;
+                    (payload, channel)
+                }
+            }
+
+            impl<B> CircReadDma<B, u8> for $rxdma
+            where
+                &'static mut [B; 2]: StaticWriteBuffer<Word = u8>,
+                B: 'static,
+            {
+                fn circ_read(mut self, mut buffer: &'static mut [B; 2]) -> CircBuffer<B, Self> {
+                    // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                    // until the end of the transfer.
+                    let (ptr, len) = unsafe { buffer.static_write_buffer() };
+                    unsafe {
+                        self.channel.set_peripheral_address(&(*$USARTX::ptr()).data as *const _ as u32, false);
+                        self.channel.set_memory_address(ptr as u32, true);
+                    }
+                    self.channel.set_transfer_length(len);
+
+                    atomic::compiler_fence(Ordering::Release);
+                    self.channel.configure(ChannelConfig::default()
+                        .priority(Priority::Medium)
+                        .word::<u8>()
+                        .memory_increment(true)
+                        .circular(true)
+                    );
+                    self.channel.ctl().modify(|_, w| w.m2m().clear_bit().dir().clear_bit());
+                    self.start();
+
+                    CircBuffer::new(buffer, self)
+                }
+            }
+
+            impl<B> ReadDma<B, u8> for $rxdma
+            where
+                B: StaticWriteBuffer<Word = u8>,
+            {
+                fn read(mut self, mut buffer: B) -> Transfer<W, B, Self> {
+                    // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                    // until the end of the transfer.
+                    let (ptr, len) = unsafe { buffer.static_write_buffer() };
+                    unsafe {
+                        self.channel.set_peripheral_address(&(*$USARTX::ptr()).data as *const _ as u32, false);
+                        self.channel.set_memory_address(ptr as u32, true);
+                    }
+                    self.channel.set_transfer_length(len);
+
+                    atomic::compiler_fence(Ordering::Release);
+                    self.channel.configure(ChannelConfig::default()
+                        .priority(Priority::Medium)
+                        .word::<u8>()
+                        .memory_increment(true)
+                    );
+                    self.channel.ctl().modify(|_, w| w.m2m().clear_bit().dir().clear_bit());
+                    self.start();
+
+                    Transfer::w(buffer, self)
+                }
+            }
+
+            impl<B> WriteDma<B, u8> for $txdma
+            where
+                B: StaticReadBuffer<Word = u8>,
+            {
+                fn write(mut self, buffer: B) -> Transfer<R, B, Self> {
+                    // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                    // until the end of the transfer.
+                    let (ptr, len) = unsafe { buffer.static_read_buffer() };
+                    unsafe {
+                        self.channel.set_peripheral_address(&(*$USARTX::ptr()).data as *const _ as u32, false);
+                        self.channel.set_memory_address(ptr as u32, true);
+                    }
+                    self.channel.set_transfer_length(len);
+
+                    atomic::compiler_fence(Ordering::Release);
+                    self.channel.configure(ChannelConfig::default()
+                        .priority(Priority::Medium)
+                        .word::<u8>()
+                        .memory_increment(true)
+                    );
+                    self.channel.ctl().modify(|_, w| w.m2m().clear_bit().dir().set_bit());
+                    self.start();
+
+                    Transfer::r(buffer, self)
+                }
+            }
+        )+
+    }
+}
+
+serialdma! {
+    USART0: (RxDma0, TxDma0, dma0::C4, dma0::C3),
+    USART1: (RxDma1, TxDma1, dma0::C5, dma0::C6),
+    USART2: (RxDma2, TxDma2, dma0::C2, dma0::C1),
+}
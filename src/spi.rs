@@ -10,7 +10,13 @@ use crate::gpio::{Alternate, Floating, Input, PushPull};
 use crate::rcu::{Rcu, Enable, Reset, BaseFrequency};
 use crate::time::Hertz;
 use crate::afio::{Afio, Remap};
+use crate::dma::{
+    dma0, ChannelConfig, Priority, ReadDma, ReadWriteDma, Receive, RxDma, RxTxDma, Transfer,
+    TransferPayload, Transmit, TxDma, WriteDma, R, W,
+};
 use core::ops::Deref;
+use core::sync::atomic::{self, Ordering};
+use embedded_dma::{StaticReadBuffer, StaticWriteBuffer};
 
 /// SPI error
 #[derive(Debug)]
@@ -222,3 +228,259 @@ impl<SPI: SpiX, PINS> crate::hal::spi::FullDuplex<u8> for Spi<SPI, PINS> {
 impl<SPI: SpiX, PINS> crate::hal::blocking::spi::transfer::Default<u8> for Spi<SPI, PINS> {}
 
 impl<SPI: SpiX, PINS> crate::hal::blocking::spi::write::Default<u8> for Spi<SPI, PINS> {}
+
+/// SPI transmitter with a DMA channel attached
+pub type SpiTxDma<SPI, PINS, CHANNEL> = TxDma<Spi<SPI, PINS>, CHANNEL>;
+
+/// SPI receiver with a DMA channel attached
+///
+/// Reception runs the bus in receive-only mode, in which the master clocks continuously until
+/// the transfer is stopped. A few frames past the end of the buffer may therefore be clocked in
+/// and discarded.
+pub type SpiRxDma<SPI, PINS, CHANNEL> = RxDma<Spi<SPI, PINS>, CHANNEL>;
+
+/// Full-duplex SPI with receive and transmit DMA channels attached
+pub type SpiRxTxDma<SPI, PINS, RXCHANNEL, TXCHANNEL> = RxTxDma<Spi<SPI, PINS>, RXCHANNEL, TXCHANNEL>;
+
+impl<SPI: SpiX, PINS> Spi<SPI, PINS> {
+    /// Waits for the last frame to be shifted out
+    fn wait_idle(&self) {
+        while self.spi.stat.read().tbe().bit_is_clear() {}
+        while self.spi.stat.read().trans().bit_is_set() {}
+    }
+
+    /// Waits for the last frame to be shifted out and discards received data and the overrun
+    /// flag left by DMA transfers
+    fn finish_dma(&mut self) {
+        self.wait_idle();
+        // Reading DATA then STAT clears RXORERR
+        let _ = self.spi.data.read();
+        let _ = self.spi.stat.read();
+    }
+}
+
+macro_rules! spi_dma {
+    ($SPIX:ident, $RXCH:ty, $TXCH:ty) => {
+        impl<PINS> Transmit for SpiTxDma<$SPIX, PINS, $TXCH> {
+            type TxChannel = $TXCH;
+            type ReceivedWord = u8;
+        }
+
+        impl<PINS> Receive for SpiRxDma<$SPIX, PINS, $RXCH> {
+            type RxChannel = $RXCH;
+            type TransmittedWord = u8;
+        }
+
+        impl<PINS> Transmit for SpiRxTxDma<$SPIX, PINS, $RXCH, $TXCH> {
+            type TxChannel = $TXCH;
+            type ReceivedWord = u8;
+        }
+
+        impl<PINS> Receive for SpiRxTxDma<$SPIX, PINS, $RXCH, $TXCH> {
+            type RxChannel = $RXCH;
+            type TransmittedWord = u8;
+        }
+
+        impl<PINS> Spi<$SPIX, PINS> {
+            /// Attaches the transmit DMA channel of this SPI
+            pub fn with_tx_dma(self, channel: $TXCH) -> SpiTxDma<$SPIX, PINS, $TXCH> {
+                self.spi.ctl1.modify(|_, w| w.dmaten().clear_bit());
+                SpiTxDma { payload: self, channel }
+            }
+
+            /// Attaches the receive DMA channel of this SPI
+            pub fn with_rx_dma(self, channel: $RXCH) -> SpiRxDma<$SPIX, PINS, $RXCH> {
+                self.spi.ctl1.modify(|_, w| w.dmaren().clear_bit());
+                SpiRxDma { payload: self, channel }
+            }
+
+            /// Attaches both DMA channels of this SPI for full-duplex transfers
+            pub fn with_rx_tx_dma(
+                self,
+                rxchannel: $RXCH,
+                txchannel: $TXCH,
+            ) -> SpiRxTxDma<$SPIX, PINS, $RXCH, $TXCH> {
+                self.spi.ctl1.modify(|_, w| w.dmaren().clear_bit().dmaten().clear_bit());
+                SpiRxTxDma { payload: self, rxchannel, txchannel }
+            }
+        }
+
+        impl<PINS> TransferPayload for SpiTxDma<$SPIX, PINS, $TXCH> {
+            fn start(&mut self) {
+                self.channel.start();
+                self.payload.spi.ctl1.modify(|_, w| w.dmaten().set_bit());
+            }
+            fn stop(&mut self) {
+                self.payload.spi.ctl1.modify(|_, w| w.dmaten().clear_bit());
+                self.channel.stop();
+                // The DMA is done before the last byte leaves the shift register
+                self.payload.wait_idle();
+            }
+        }
+
+        impl<PINS> TransferPayload for SpiRxDma<$SPIX, PINS, $RXCH> {
+            fn start(&mut self) {
+                self.payload.spi.ctl1.modify(|_, w| w.dmaren().set_bit());
+                self.channel.start();
+                // Receive-only mode starts the clock
+                self.payload.spi.ctl0.modify(|_, w| w.ro().set_bit());
+            }
+            fn stop(&mut self) {
+                // Disabling the SPI is the only way to stop the receive-only clock
+                self.payload.spi.ctl0.modify(|_, w| w.spien().clear_bit());
+                self.payload.spi.ctl0.modify(|_, w| w.ro().clear_bit());
+                self.payload.spi.ctl1.modify(|_, w| w.dmaren().clear_bit());
+                self.channel.stop();
+                let _ = self.payload.spi.data.read();
+                let _ = self.payload.spi.stat.read();
+                self.payload.spi.ctl0.modify(|_, w| w.spien().set_bit());
+            }
+        }
+
+        impl<PINS> TransferPayload for SpiRxTxDma<$SPIX, PINS, $RXCH, $TXCH> {
+            fn start(&mut self) {
+                self.payload.spi.ctl1.modify(|_, w| w.dmaren().set_bit());
+                self.rxchannel.start();
+                self.txchannel.start();
+                self.payload.spi.ctl1.modify(|_, w| w.dmaten().set_bit());
+            }
+            fn stop(&mut self) {
+                self.payload.spi.ctl1.modify(|_, w| w.dmaten().clear_bit().dmaren().clear_bit());
+                self.txchannel.stop();
+                self.rxchannel.stop();
+            }
+        }
+
+        impl<PINS> SpiTxDma<$SPIX, PINS, $TXCH> {
+            /// Stops any transfer and returns the blocking SPI and the DMA channel
+            pub fn release(mut self) -> (Spi<$SPIX, PINS>, $TXCH) {
+                self.stop();
+                let SpiTxDma { mut payload, channel } = self;
+                payload.finish_dma();
+                (payload, channel)
+            }
+        }
+
+        impl<PINS> SpiRxDma<$SPIX, PINS, $RXCH> {
+            /// Stops any transfer and returns the blocking SPI and the DMA channel
+            pub fn release(mut self) -> (Spi<$SPIX, PINS>, $RXCH) {
+                self.stop();
+                let SpiRxDma { mut payload, channel } = self;
+                payload.finish_dma();
+                (payload, channel)
+            }
+        }
+
+        impl<PINS> SpiRxTxDma<$SPIX, PINS, $RXCH, $TXCH> {
+            /// Stops any transfer and returns the blocking SPI and the DMA channels
+            pub fn release(mut self) -> (Spi<$SPIX, PINS>, $RXCH, $TXCH) {
+                self.stop();
+                let SpiRxTxDma { mut payload, rxchannel, txchannel } = self;
+                payload.finish_dma();
+                (payload, rxchannel, txchannel)
+            }
+        }
+
+        impl<B, PINS> WriteDma<B, u8> for SpiTxDma<$SPIX, PINS, $TXCH>
+        where
+            B: StaticReadBuffer<Word = u8>,
+        {
+            fn write(mut self, buffer: B) -> Transfer<R, B, Self> {
+                // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                // until the end of the transfer.
+                let (ptr, len) = unsafe { buffer.static_read_buffer() };
+                unsafe {
+                    self.channel.set_peripheral_address(&(*$SPIX::ptr()).data as *const _ as u32, false);
+                    self.channel.set_memory_address(ptr as u32, true);
+                }
+                self.channel.set_transfer_length(len);
+
+                atomic::compiler_fence(Ordering::Release);
+                self.channel.configure(ChannelConfig::default()
+                    .priority(Priority::Medium)
+                    .word::<u8>()
+                    .memory_increment(true)
+                );
+                self.channel.ctl().modify(|_, w| w.m2m().clear_bit().dir().set_bit());
+                self.start();
+
+                Transfer::r(buffer, self)
+            }
+        }
+
+        impl<B, PINS> ReadDma<B, u8> for SpiRxDma<$SPIX, PINS, $RXCH>
+        where
+            B: StaticWriteBuffer<Word = u8>,
+        {
+            fn read(mut self, mut buffer: B) -> Transfer<W, B, Self> {
+                // NOTE(unsafe) We own the buffer now and we won't call other `&mut` on it
+                // until the end of the transfer.
+                let (ptr, len) = unsafe { buffer.static_write_buffer() };
+                unsafe {
+                    self.channel.set_peripheral_address(&(*$SPIX::ptr()).data as *const _ as u32, false);
+                    self.channel.set_memory_address(ptr as u32, true);
+                }
+                self.channel.set_transfer_length(len);
+
+                atomic::compiler_fence(Ordering::Release);
+                self.channel.configure(ChannelConfig::default()
+                    .priority(Priority::Medium)
+                    .word::<u8>()
+                    .memory_increment(true)
+                );
+                self.channel.ctl().modify(|_, w| w.m2m().clear_bit().dir().clear_bit());
+                self.start();
+
+                Transfer::w(buffer, self)
+            }
+        }
+
+        impl<RXB, TXB, PINS> ReadWriteDma<RXB, TXB, u8> for SpiRxTxDma<$SPIX, PINS, $RXCH, $TXCH>
+        where
+            RXB: StaticWriteBuffer<Word = u8>,
+            TXB: StaticReadBuffer<Word = u8>,
+        {
+            fn read_write(
+                mut self,
+                mut rx_buffer: RXB,
+                tx_buffer: TXB,
+            ) -> Transfer<W, (RXB, TXB), Self> {
+                // NOTE(unsafe) We own the buffers now and we won't call other `&mut` on them
+                // until the end of the transfer.
+                let (rx_ptr, rx_len) = unsafe { rx_buffer.static_write_buffer() };
+                let (tx_ptr, tx_len) = unsafe { tx_buffer.static_read_buffer() };
+                assert_eq!(rx_len, tx_len, "buffers must have the same length");
+
+                unsafe {
+                    self.rxchannel.set_peripheral_address(&(*$SPIX::ptr()).data as *const _ as u32, false);
+                    self.rxchannel.set_memory_address(rx_ptr as u32, true);
+                    self.txchannel.set_peripheral_address(&(*$SPIX::ptr()).data as *const _ as u32, false);
+                    self.txchannel.set_memory_address(tx_ptr as u32, true);
+                }
+                self.rxchannel.set_transfer_length(rx_len);
+                self.txchannel.set_transfer_length(tx_len);
+
+                atomic::compiler_fence(Ordering::Release);
+                // Reception gets the higher priority so received frames are never overrun
+                self.rxchannel.configure(ChannelConfig::default()
+                    .priority(Priority::High)
+                    .word::<u8>()
+                    .memory_increment(true)
+                );
+                self.rxchannel.ctl().modify(|_, w| w.m2m().clear_bit().dir().clear_bit());
+                self.txchannel.configure(ChannelConfig::default()
+                    .priority(Priority::Medium)
+                    .word::<u8>()
+                    .memory_increment(true)
+                );
+                self.txchannel.ctl().modify(|_, w| w.m2m().clear_bit().dir().set_bit());
+                self.start();
+
+                Transfer::w((rx_buffer, tx_buffer), self)
+            }
+        }
+    };
+}
+
+spi_dma!(SPI0, dma0::C1, dma0::C2);
+spi_dma!(SPI1, dma0::C3, dma0::C4);
@@ -0,0 +1,165 @@
+//! Exception and NMI diagnostics
+//!
+//! With the ECLIC enabled, interrupts are dispatched through `mtvt`/`mtvt2` and only exceptions
+//! and the NMI (which shares `mtvec` through `mmisc_ctl`) enter `_start_trap`. A hook installed
+//! with [`set_exception_hook`] receives the decoded trap CSRs and the registers saved on entry.
+//! Without a hook the trap is passed on to `_start_trap_rust` from `riscv-rt`.
+//!
+//! ```no_run
+//! use gd32vf103xx_hal::trap::{self, ExceptionInfo, TrapFrame};
+//!
+//! fn on_exception(info: &ExceptionInfo, frame: &mut TrapFrame) {
+//!     // Log `info.cause`, `info.mepc`, `info.mtval` and `frame.ra`, then reset
+//!     loop {}
+//! }
+//!
+//! trap::set_exception_hook(on_exception);
+//! ```
+
+use core::arch::asm;
+use core::cell::Cell;
+use riscv::interrupt::{self, Mutex};
+use riscv::register::{mcause, mepc, mtval};
+
+/// `mcause.EXCCODE` of the NMI when `mmisc_ctl.NMI_CAUSE_FFF` is set
+const NMI_EXCCODE: usize = 0xFFF;
+const EXCCODE_MASK: usize = 0xFFF;
+
+/// Registers saved by `_start_trap`
+///
+/// The layout matches `riscv_rt::TrapFrame`. Values written here are restored on return.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct TrapFrame {
+    pub ra: usize,
+    pub t0: usize,
+    pub t1: usize,
+    pub t2: usize,
+    pub t3: usize,
+    pub t4: usize,
+    pub t5: usize,
+    pub t6: usize,
+    pub a0: usize,
+    pub a1: usize,
+    pub a2: usize,
+    pub a3: usize,
+    pub a4: usize,
+    pub a5: usize,
+    pub a6: usize,
+    pub a7: usize,
+}
+
+/// Cause of a trap entering `_start_trap`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Cause {
+    Exception(mcause::Exception),
+    Nmi,
+}
+
+/// Trap type reported by the `msubm` register
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TrapType {
+    /// Not in a trap handler
+    None = 0,
+    Interrupt = 1,
+    Exception = 2,
+    Nmi = 3,
+}
+
+impl TrapType {
+    fn from_bits(bits: usize) -> Self {
+        match bits & 0b11 {
+            0 => TrapType::None,
+            1 => TrapType::Interrupt,
+            2 => TrapType::Exception,
+            _ => TrapType::Nmi,
+        }
+    }
+}
+
+/// Decoded trap CSRs
+#[derive(Copy, Clone, Debug)]
+pub struct ExceptionInfo {
+    /// Decoded `mcause.EXCCODE`
+    pub cause: Cause,
+    /// Raw `mcause`
+    pub mcause: usize,
+    /// Address of the trapping instruction
+    pub mepc: usize,
+    /// Faulting address or instruction, depending on the cause
+    pub mtval: usize,
+    /// Trap type before this trap was taken (`msubm.PTYP`)
+    pub previous: TrapType,
+}
+
+impl ExceptionInfo {
+    /// Reads the trap CSRs
+    ///
+    /// Only meaningful from within the exception hook.
+    pub fn read() -> Self {
+        let mcause = mcause::read().bits();
+        let code = mcause & EXCCODE_MASK;
+        let cause = if code == NMI_EXCCODE {
+            Cause::Nmi
+        } else {
+            Cause::Exception(mcause::Exception::from(code))
+        };
+
+        let msubm: usize;
+        // NOTE(unsafe) read of the Bumblebee msubm CSR
+        unsafe { asm!("csrr {0}, 0x7c4", out(reg) msubm) };
+
+        ExceptionInfo {
+            cause,
+            mcause,
+            mepc: mepc::read(),
+            mtval: mtval::read(),
+            previous: TrapType::from_bits(msubm >> 8),
+        }
+    }
+
+    /// Returns `true` if the trap is the NMI
+    pub fn is_nmi(&self) -> bool {
+        self.cause == Cause::Nmi
+    }
+}
+
+/// Exception hook called with the decoded CSRs and the saved registers
+///
+/// If the hook returns, execution resumes at `mepc`. `ExceptionInfo` is a copy, so changing
+/// `info.mepc` has no effect; to skip a faulting instruction, call [`set_mepc`] from the hook.
+pub type ExceptionHook = fn(&ExceptionInfo, &mut TrapFrame);
+
+static HOOK: Mutex<Cell<Option<ExceptionHook>>> = Mutex::new(Cell::new(None));
+
+/// Installs the exception and NMI hook
+pub fn set_exception_hook(hook: ExceptionHook) {
+    interrupt::free(|cs| HOOK.borrow(cs).set(Some(hook)));
+}
+
+/// Removes the exception hook, passing traps on to `_start_trap_rust` again
+pub fn clear_exception_hook() {
+    interrupt::free(|cs| HOOK.borrow(cs).set(None));
+}
+
+/// Sets the address execution resumes at when the exception hook returns
+///
+/// Only meaningful from within the exception hook. Instructions are 2 bytes long if the low two
+/// bits of their first halfword aren't `0b11` and 4 bytes otherwise.
+pub fn set_mepc(addr: usize) {
+    mepc::write(addr);
+}
+
+unsafe extern "C" {
+    fn _start_trap_rust(trap_frame: *const TrapFrame);
+}
+
+#[doc(hidden)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn _start_trap_hal(trap_frame: *mut TrapFrame) {
+    match interrupt::free(|cs| HOOK.borrow(cs).get()) {
+        // NOTE(unsafe) `trap_frame` points to the registers pushed by `_start_trap`
+        Some(hook) => hook(&ExceptionInfo::read(), unsafe { &mut *trap_frame }),
+        None => unsafe { _start_trap_rust(trap_frame) },
+    }
+}
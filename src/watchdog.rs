@@ -3,15 +3,17 @@
 use crate::{
     hal::watchdog::{Watchdog, WatchdogEnable},
     pac::{DBG, FWDGT},
-    time::MilliSeconds,
+    rcu::Rcu,
+    time::{Hertz, MilliSeconds},
 };
 
 /// Wraps the Free Watchdog Timer (FWDGT) peripheral
 pub struct FreeWatchdog {
     fwdgt: FWDGT,
+    irc40k: Hertz,
 }
 
-const IRC40K_KHZ: u32 = 40;
+const IRC40K_HERTZ: u32 = 40_000;
 
 const MAX_PR: u8 = 8;
 const MAX_RL: u16 = 0xFFF;
@@ -23,7 +25,22 @@ const CMD_START: u16 = 0xCCCC;
 impl FreeWatchdog {
     /// Wrap and start the watchdog
     pub fn new(fwdgt: FWDGT) -> Self {
-        FreeWatchdog { fwdgt }
+        FreeWatchdog { fwdgt, irc40k: Hertz(IRC40K_HERTZ) }
+    }
+
+    /// Sets the IRC40K frequency used to compute prescaler and reload values
+    ///
+    /// Defaults to the nominal 40 kHz. Pass the measured
+    /// [`Clocks::irc40k`](crate::rcu::Clocks::irc40k) after
+    /// [`calibrate`](crate::calibration::calibrate) for accurate timeouts. Takes effect on the
+    /// next call to `start`.
+    pub fn set_irc40k_frequency(&mut self, freq: impl Into<Hertz>) {
+        self.irc40k = freq.into();
+    }
+
+    /// Returns `true` if the last reset was caused by the free watchdog
+    pub fn caused_reset(rcu: &Rcu) -> bool {
+        rcu.reset_reason().free_watchdog()
     }
 
     /// Free watchdog stopped when core is halted
@@ -33,11 +50,11 @@ impl FreeWatchdog {
 
     fn setup(&self, timeout_ms: u32) {
         let mut pr = 0;
-        while pr < MAX_PR && Self::timeout_period(pr, MAX_RL) < timeout_ms {
+        while pr < MAX_PR && self.timeout_period(pr, MAX_RL) < timeout_ms {
             pr += 1;
         }
 
-        let max_period = Self::timeout_period(pr, MAX_RL);
+        let max_period = self.timeout_period(pr, MAX_RL);
         let max_rl = u32::from(MAX_RL);
         let rl = (timeout_ms * max_rl / max_period).min(max_rl) as u16;
 
@@ -57,7 +74,7 @@ impl FreeWatchdog {
 
         let pr = self.fwdgt.psc.read().psc().bits();
         let rl = self.fwdgt.rld.read().rld().bits();
-        let ms = Self::timeout_period(pr, rl);
+        let ms = self.timeout_period(pr, rl);
 
         MilliSeconds(ms)
     }
@@ -65,7 +82,7 @@ impl FreeWatchdog {
     /// pr: Prescaler divider bits, rl: reload value
     ///
     /// Returns ms
-    fn timeout_period(pr: u8, rl: u16) -> u32 {
+    fn timeout_period(&self, pr: u8, rl: u16) -> u32 {
         let divider: u32 = match pr {
             0b000 => 4,
             0b001 => 8,
@@ -77,7 +94,7 @@ impl FreeWatchdog {
             0b111 => 256,
             _ => panic!("Invalid FWDGT prescaler divider"),
         };
-        (u32::from(rl) + 1) * divider / IRC40K_KHZ
+        ((u64::from(rl) + 1) * u64::from(divider) * 1000 / u64::from(self.irc40k.0)) as u32
     }
 
     fn access_registers<A, F: FnMut(&FWDGT) -> A>(&self, mut f: F) -> A {